@@ -0,0 +1,58 @@
+//! Compares building retry configurations from associated constants and from
+//! trait methods.
+//!
+//! Run with `cargo bench --bench config`.
+use retryable_error::{Retryable, SimpleRetryable};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use tryhard::RetryPolicy;
+
+const ITERATIONS: u32 = 10_000_000;
+
+struct ConstError;
+
+impl SimpleRetryable for ConstError {
+    const MAX_RETRIES: u32 = 7;
+    const INITIAL_DELAY_MS: u64 = 250;
+}
+
+struct MethodError;
+
+impl Retryable for MethodError {
+    fn max_retries() -> u32 {
+        black_box(7)
+    }
+
+    fn default_initial_delay() -> Duration {
+        black_box(Duration::from_millis(250))
+    }
+
+    fn log_level() -> Option<log::Level> {
+        black_box(None)
+    }
+
+    fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+        None
+    }
+}
+
+fn run<E: Retryable>(name: &str) {
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        black_box(E::retry_config());
+    }
+
+    let elapsed = start.elapsed();
+
+    println!(
+        "{}: {:.2} ns/iter",
+        name,
+        elapsed.as_nanos() as f64 / f64::from(ITERATIONS)
+    );
+}
+
+fn main() {
+    run::<ConstError>("associated constants");
+    run::<MethodError>("trait methods");
+}
@@ -0,0 +1,134 @@
+//! Retries calls to a simulated flaky service, exercising custom policies,
+//! logging, and exhaustion through the public API.
+//!
+//! Run with `cargo run --example flaky`.
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use retryable_error::{retry_future, Retryable};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use tryhard::RetryPolicy;
+
+#[derive(thiserror::Error, Debug)]
+enum ServiceError {
+    #[error("Request timed out")]
+    Timeout,
+    #[error("Rate limited for {0:?}")]
+    RateLimited(Duration),
+    #[error("Not found")]
+    NotFound,
+}
+
+impl Retryable for ServiceError {
+    fn max_retries() -> u32 {
+        4
+    }
+
+    fn default_initial_delay() -> Duration {
+        Duration::from_millis(10)
+    }
+
+    fn log_level() -> Option<Level> {
+        Some(Level::Warn)
+    }
+
+    fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+        match self {
+            Self::Timeout => None,
+            Self::RateLimited(delay) => Some(RetryPolicy::Delay(*delay)),
+            Self::NotFound => Some(RetryPolicy::Break),
+        }
+    }
+}
+
+/// An in-process service that fails according to a fixed script, and then
+/// succeeds.
+struct FlakyService {
+    failures: Vec<fn() -> ServiceError>,
+    calls: AtomicU32,
+}
+
+impl FlakyService {
+    fn new(failures: Vec<fn() -> ServiceError>) -> Self {
+        Self {
+            failures,
+            calls: AtomicU32::new(0),
+        }
+    }
+
+    async fn call(&self) -> Result<u32, ServiceError> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst);
+
+        match self.failures.get(call as usize) {
+            Some(failure) => Err(failure()),
+            None => Ok(call + 1),
+        }
+    }
+}
+
+/// Prints log messages from the retry loop to standard error.
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        eprintln!("[{}] {}", record.level(), record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+async fn run(name: &str, service: FlakyService) -> Result<u32, ServiceError> {
+    let started = Instant::now();
+    let result = retry_future(|| service.call()).await;
+
+    println!(
+        "{}: {:?} after {} calls ({:?})",
+        name,
+        result,
+        service.calls.load(Ordering::SeqCst),
+        started.elapsed()
+    );
+
+    result
+}
+
+#[tokio::main]
+async fn main() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Warn);
+
+    let recovered = run(
+        "recovered",
+        FlakyService::new(vec![|| ServiceError::Timeout, || ServiceError::Timeout]),
+    )
+    .await;
+    assert_eq!(recovered.ok(), Some(3));
+
+    let rate_limited = run(
+        "rate limited",
+        FlakyService::new(vec![|| {
+            ServiceError::RateLimited(Duration::from_millis(50))
+        }]),
+    )
+    .await;
+    assert_eq!(rate_limited.ok(), Some(2));
+
+    let broken = run(
+        "broken",
+        FlakyService::new(vec![|| ServiceError::Timeout, || ServiceError::NotFound]),
+    )
+    .await;
+    assert!(matches!(broken, Err(ServiceError::NotFound)));
+
+    let exhausted = run(
+        "exhausted",
+        FlakyService::new(vec![|| ServiceError::Timeout; 10]),
+    )
+    .await;
+    assert!(matches!(exhausted, Err(ServiceError::Timeout)));
+}
@@ -0,0 +1,215 @@
+//! Interrupting the delays of a retry loop.
+use crate::{effective_max_retries, trace_start, RetryDriver, RetryError, RetryStep, Retryable};
+use log::log;
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::pin;
+use std::time::Duration;
+use tokio::sync::mpsc::Receiver;
+
+/// Execute a future with retries, aborting a pending delay if an error that
+/// satisfies the predicate arrives on the given channel.
+///
+/// The operation's own errors are handled as usual (so a `Break` policy
+/// already takes effect at the next attempt boundary). The channel is for
+/// errors observed elsewhere, such as another task finding out that the
+/// resource is permanently gone. While the loop is waiting to retry, each
+/// error received is checked against the predicate, and the first one that
+/// satisfies it ends the loop immediately and is returned. Errors that don't
+/// satisfy it are discarded, and errors that arrive during an attempt are
+/// checked when the next delay starts. If the channel is closed, the delays
+/// can no longer be interrupted.
+pub async fn retry_future_abortable<F, Fut, T, E, P>(
+    f: F,
+    signals: &mut Receiver<E>,
+    abort_on: P,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+    P: Fn(&E) -> bool,
+{
+    retry_future_abortable_result(f, signals, abort_on)
+        .await
+        .map_err(RetryError::into_error)
+}
+
+/// Execute a future with retries that can be aborted (as for
+/// `retry_future_abortable`), returning an error that describes how the loop
+/// gave up.
+///
+/// An abort fails with `RetryError::Cancelled`, holding the error that
+/// aborted the loop.
+pub async fn retry_future_abortable_result<F, Fut, T, E, P>(
+    mut f: F,
+    signals: &mut Receiver<E>,
+    abort_on: P,
+) -> Result<T, RetryError<E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+    P: Fn(&E) -> bool,
+{
+    let mut driver = RetryDriver::new();
+
+    if let Some(error) = driver.not_attempted() {
+        return Err(RetryError::NotAttempted { error });
+    }
+
+    trace_start::<E>(effective_max_retries::<E>());
+
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+
+        match driver.next(f().await) {
+            RetryStep::Done(result) => {
+                return result.map_err(|error| {
+                    let reason = driver
+                        .stop_reason()
+                        .expect("the driver records the reason for stopping");
+
+                    RetryError::stopped(error, reason, attempts)
+                });
+            }
+            RetryStep::WaitThen(delay) => {
+                if let Some(error) = wait_unless_aborted(delay, signals, &abort_on).await {
+                    match E::log_level() {
+                        Some(level) if E::log_error_value() => {
+                            log!(level, "Aborting retry delay after error: {:?}", error);
+                        }
+                        Some(level) => log!(level, "Aborting retry delay"),
+                        None => {}
+                    }
+
+                    return Err(RetryError::Cancelled { error });
+                }
+
+                driver.before_retry(delay).await;
+            }
+        }
+    }
+}
+
+/// Wait for the delay, returning early with the first received error that
+/// satisfies the predicate.
+async fn wait_unless_aborted<E, P: Fn(&E) -> bool>(
+    delay: Duration,
+    signals: &mut Receiver<E>,
+    abort_on: &P,
+) -> Option<E> {
+    let mut sleep = pin!(tokio::time::sleep(delay));
+    let mut open = true;
+
+    loop {
+        tokio::select! {
+            () = &mut sleep => return None,
+            signal = signals.recv(), if open => match signal {
+                Some(error) if abort_on(&error) => return Some(error),
+                Some(_) => {}
+                None => open = false,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::sync::mpsc::channel;
+    use tokio::time::Instant;
+    use tryhard::RetryPolicy;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub enum Error {
+        Transient,
+        Gone,
+    }
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_secs(60)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn abort_mid_delay() {
+        let (sender, mut receiver) = channel(4);
+        let attempts = AtomicU32::new(0);
+        let started = Instant::now();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            sender.send(Error::Transient).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            sender.send(Error::Gone).await.unwrap();
+        });
+
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(Error::Transient)
+        };
+
+        let result =
+            retry_future_abortable(future, &mut receiver, |error| *error == Error::Gone).await;
+
+        assert_eq!(result, Err(Error::Gone));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(started.elapsed(), Duration::from_secs(20));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn closed_channel() {
+        let (sender, mut receiver) = channel(4);
+        drop(sender);
+
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(Error::Transient)
+        };
+
+        let result = retry_future_abortable(future, &mut receiver, |_| true).await;
+
+        assert_eq!(result, Err(Error::Transient));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled() {
+        let (sender, mut receiver) = channel(4);
+        sender.send(Error::Gone).await.unwrap();
+
+        let future = || async { Err::<(), _>(Error::Transient) };
+        let result = retry_future_abortable_result(future, &mut receiver, |_| true).await;
+
+        assert_eq!(result, Err(RetryError::Cancelled { error: Error::Gone }));
+
+        drop(sender);
+        let result = retry_future_abortable_result(future, &mut receiver, |_| true).await;
+
+        assert_eq!(
+            result,
+            Err(RetryError::Exhausted {
+                error: Error::Transient,
+                attempts: 4
+            })
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Suppressing retries when most recent attempts are failing.
+use crate::{config_with_backoff, retry_with_config, ErrorBackoff, Retryable};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tryhard::{backoff_strategies::BackoffStrategy, RetryPolicy};
+
+/// A gate that suppresses retries while the recent failure rate is too high.
+///
+/// The gate records the outcome of every attempt made through it in a
+/// sliding window of the last `window` attempts. Once the window is full, if
+/// the fraction of failures in it is greater than `max_failure_rate`, failed
+/// attempts aren't retried (though first attempts are still made, and their
+/// outcomes still update the window). This is a lightweight alternative to a
+/// `CircuitBreaker` for when the downstream is clearly down and retries would
+/// only add load.
+///
+/// A gate is typically shared by all operations with the same error type, and
+/// clones share the same state.
+#[derive(Clone, Debug)]
+pub struct AdaptiveGate {
+    outcomes: Arc<Mutex<VecDeque<bool>>>,
+    window: usize,
+    max_failure_rate: f64,
+}
+
+impl AdaptiveGate {
+    /// Create a gate with an empty window of the given size.
+    pub fn new(window: usize, max_failure_rate: f64) -> Self {
+        Self {
+            outcomes: Arc::new(Mutex::new(VecDeque::with_capacity(window))),
+            window,
+            max_failure_rate,
+        }
+    }
+
+    /// Return the fraction of failures in the window (or an empty value if
+    /// it isn't full yet).
+    pub fn failure_rate(&self) -> Option<f64> {
+        let outcomes = self.lock();
+
+        if outcomes.len() < self.window || self.window == 0 {
+            None
+        } else {
+            let failures = outcomes.iter().filter(|success| !**success).count();
+
+            Some(failures as f64 / outcomes.len() as f64)
+        }
+    }
+
+    /// Indicate whether retries are currently suppressed.
+    pub fn is_suppressing(&self) -> bool {
+        self.failure_rate()
+            .is_some_and(|failure_rate| failure_rate > self.max_failure_rate)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<bool>> {
+        self.outcomes
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn record(&self, success: bool) {
+        let mut outcomes = self.lock();
+
+        if outcomes.len() >= self.window {
+            outcomes.pop_front();
+        }
+
+        if self.window > 0 {
+            outcomes.push_back(success);
+        }
+    }
+}
+
+/// Execute a future with retries, recording each outcome in the gate and not
+/// retrying while the gate is suppressing retries.
+///
+/// The gate is checked after each failed attempt has been recorded, before
+/// the usual policy is consulted.
+pub fn retry_future_adaptive<F, Fut, T, E>(
+    mut f: F,
+    gate: &AdaptiveGate,
+) -> impl Future<Output = Result<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let backoff_gate = gate.clone();
+    let gate = gate.clone();
+
+    retry_with_config(
+        move || {
+            let future = f();
+            let gate = gate.clone();
+
+            async move {
+                let result = future.await;
+                gate.record(result.is_ok());
+
+                result
+            }
+        },
+        move || {
+            config_with_backoff::<E, _>(GateBackoff {
+                backoff: E::new_backoff(),
+                gate: backoff_gate,
+            })
+        },
+    )
+}
+
+struct GateBackoff<E> {
+    backoff: ErrorBackoff<E>,
+    gate: AdaptiveGate,
+}
+
+impl<'a, E: Retryable> BackoffStrategy<'a, E> for GateBackoff<E> {
+    type Output = RetryPolicy;
+
+    fn delay(&mut self, attempt: u32, error: &'a E) -> RetryPolicy {
+        if self.gate.is_suppressing() {
+            RetryPolicy::Break
+        } else {
+            self.backoff.delay(attempt, error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestError;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    type Error = TestError<10>;
+
+    #[test]
+    fn window() {
+        let gate = AdaptiveGate::new(4, 0.5);
+        gate.record(false);
+        gate.record(false);
+        gate.record(false);
+        assert_eq!(gate.failure_rate(), None);
+
+        gate.record(true);
+        assert_eq!(gate.failure_rate(), Some(0.75));
+        assert!(gate.is_suppressing());
+
+        gate.record(true);
+        assert_eq!(gate.failure_rate(), Some(0.5));
+        assert!(!gate.is_suppressing());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn suppress_retries() {
+        let gate = AdaptiveGate::new(6, 0.5);
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(Error::Transient)
+        };
+
+        // The window isn't full, so the first loop uses all of its retries.
+        assert_eq!(
+            retry_future_adaptive(future, &gate).await,
+            Err(Error::Transient)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+
+        // After two more failures the window is full, and retries stop.
+        assert_eq!(
+            retry_future_adaptive(future, &gate).await,
+            Err(Error::Transient)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 6);
+        assert_eq!(
+            retry_future_adaptive(future, &gate).await,
+            Err(Error::Transient)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 7);
+
+        // Successes bring the failure rate back down.
+        for _ in 0..3 {
+            assert_eq!(
+                retry_future_adaptive(|| async { Ok::<_, Error>(()) }, &gate).await,
+                Ok(())
+            );
+        }
+        assert!(!gate.is_suppressing());
+    }
+}
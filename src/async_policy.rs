@@ -0,0 +1,143 @@
+//! Retry policies that are determined asynchronously.
+use crate::{effective_max_retries, trace_start, RetryDriver, RetryStep, Retryable};
+use std::fmt::Debug;
+use std::future::Future;
+use tryhard::RetryPolicy;
+
+/// A `Retryable` error type whose custom policy requires an async call (for
+/// example to check whether a token has been revoked).
+///
+/// The static configuration comes from the `Retryable` impl, but
+/// `retry_future_async_policy` uses this policy instead of the synchronous
+/// `Retryable::custom_retry_policy`. Implementations can use
+/// `async fn custom_retry_policy(&self)`.
+pub trait AsyncRetryable: Retryable {
+    /// Determine the custom policy for this error value, or an empty value to
+    /// use the default exponential backoff.
+    fn custom_retry_policy(&self) -> impl Future<Output = Option<RetryPolicy>>;
+}
+
+/// Execute a future with retries where the error type's policy is determined
+/// asynchronously.
+///
+/// After each failed attempt that has retries remaining, the loop awaits the
+/// error's `AsyncRetryable::custom_retry_policy` before the delay starts, so
+/// the time it takes is added to the time between attempts (and counts
+/// toward the maximum elapsed time). It isn't awaited after a success or
+/// after the last attempt.
+pub async fn retry_future_async_policy<F, Fut, T, E>(mut f: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: AsyncRetryable + Debug,
+{
+    let mut driver = RetryDriver::new();
+
+    if let Some(error) = driver.not_attempted() {
+        return Err(error);
+    }
+
+    trace_start::<E>(effective_max_retries::<E>());
+
+    loop {
+        let result = f().await;
+        let custom = match &result {
+            Err(error) if driver.retries_remaining() => {
+                AsyncRetryable::custom_retry_policy(error).await
+            }
+            _ => None,
+        };
+
+        match driver.next_with_policy(result, custom) {
+            RetryStep::Done(result) => return result,
+            RetryStep::WaitThen(delay) => {
+                driver.wait(delay).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub enum Error {
+        Unauthorized,
+        Transient,
+    }
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    impl AsyncRetryable for Error {
+        async fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            match self {
+                // Simulate a lookup that takes some time.
+                Self::Unauthorized => {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Some(RetryPolicy::Break)
+                }
+                Self::Transient => None,
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn async_policy() {
+        let attempts = AtomicU32::new(0);
+        let started = Instant::now();
+        let future = || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err::<(), _>(Error::Transient)
+            } else {
+                Err(Error::Unauthorized)
+            }
+        };
+
+        assert_eq!(
+            retry_future_async_policy(future).await,
+            Err(Error::Unauthorized)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(started.elapsed(), Duration::from_millis(100 + 200 + 50));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn not_awaited_after_last_attempt() {
+        let attempts = AtomicU32::new(0);
+        let started = Instant::now();
+        let future = || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 3 {
+                Err::<(), _>(Error::Transient)
+            } else {
+                Err(Error::Unauthorized)
+            }
+        };
+
+        assert_eq!(
+            retry_future_async_policy(future).await,
+            Err(Error::Unauthorized)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+        assert_eq!(started.elapsed(), Duration::from_millis(100 + 200 + 400));
+    }
+}
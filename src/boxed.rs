@@ -0,0 +1,161 @@
+//! Retries for boxed error trait objects.
+use crate::{retry_with_policy, retry_with_policy_from, Retryable};
+use log::Level;
+use std::error::Error;
+use std::future::Future;
+use std::time::Duration;
+use tryhard::RetryPolicy;
+
+/// Boxed errors use a fixed configuration: up to five retries, starting with a
+/// 100 millisecond delay, logged at the warning level.
+///
+/// There is no custom policy, so every error is retried with the default
+/// backoff, and every other option has its default value. Use `retry_boxed` to
+/// classify the errors, and `retry_boxed_with_config` to take the
+/// configuration from another type.
+impl Retryable for Box<dyn Error + Send + Sync> {
+    fn max_retries() -> u32 {
+        5
+    }
+
+    fn default_initial_delay() -> Duration {
+        Duration::from_millis(100)
+    }
+
+    fn log_level() -> Option<Level> {
+        Some(Level::Warn)
+    }
+
+    fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+        None
+    }
+}
+
+/// Execute a future with retries where the error is a boxed trait object,
+/// using the given function to classify errors.
+///
+/// The function can downcast the error to determine the policy, and the
+/// default backoff is used if it returns an empty value.
+///
+/// ```
+/// use retryable_error::retry_boxed;
+/// use std::error::Error;
+/// use std::io::{self, ErrorKind};
+/// use tryhard::RetryPolicy;
+///
+/// fn classify(error: &(dyn Error + 'static)) -> Option<RetryPolicy> {
+///     match error.downcast_ref::<io::Error>() {
+///         Some(error) if error.kind() == ErrorKind::NotFound => Some(RetryPolicy::Break),
+///         _ => None,
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let result = retry_boxed(
+///     || async { Err::<(), Box<dyn Error + Send + Sync>>(io::Error::from(ErrorKind::NotFound).into()) },
+///     classify,
+/// )
+/// .await;
+///
+/// assert!(result.is_err());
+/// # }
+/// ```
+pub fn retry_boxed<F, Fut, T, C>(
+    f: F,
+    classify: C,
+) -> impl Future<Output = Result<T, Box<dyn Error + Send + Sync>>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Box<dyn Error + Send + Sync>>>,
+    C: Fn(&(dyn Error + 'static)) -> Option<RetryPolicy>,
+{
+    retry_with_policy(f, move |error| classify(error.as_ref()))
+}
+
+/// Execute a future with retries where the error is a boxed trait object, as
+/// for `retry_boxed`, but using the static configuration of the given type
+/// (such as its number of retries, delays, and log level) in place of the
+/// fixed configuration for boxed errors.
+///
+/// The type's per-error options (such as its custom policy) don't apply,
+/// since the errors aren't values of that type.
+pub fn retry_boxed_with_config<S, F, Fut, T, C>(
+    f: F,
+    classify: C,
+) -> impl Future<Output = Result<T, Box<dyn Error + Send + Sync>>>
+where
+    S: Retryable,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Box<dyn Error + Send + Sync>>>,
+    C: Fn(&(dyn Error + 'static)) -> Option<RetryPolicy>,
+{
+    retry_with_policy_from::<S, _, _, _, _, _>(f, move |error| classify(error.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, ErrorKind};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn classify(error: &(dyn Error + 'static)) -> Option<RetryPolicy> {
+        match error.downcast_ref::<io::Error>() {
+            Some(error) if error.kind() == ErrorKind::NotFound => Some(RetryPolicy::Break),
+            Some(_) => Some(RetryPolicy::Delay(Duration::from_millis(1))),
+            None => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn downcast() {
+        let attempts = AtomicU32::new(0);
+
+        let future = || async {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            let kind = if attempt < 2 {
+                ErrorKind::TimedOut
+            } else {
+                ErrorKind::NotFound
+            };
+
+            Err::<(), Box<dyn Error + Send + Sync>>(io::Error::from(kind).into())
+        };
+
+        let error = retry_boxed(future, classify).await.unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<io::Error>().map(io::Error::kind),
+            Some(ErrorKind::NotFound)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[derive(Debug)]
+    pub struct Config;
+
+    impl crate::SimpleRetryable for Config {
+        const MAX_RETRIES: u32 = 1;
+        const INITIAL_DELAY_MS: u64 = 10;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn with_config() {
+        let attempts = AtomicU32::new(0);
+        let started = tokio::time::Instant::now();
+
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+
+            Err::<(), Box<dyn Error + Send + Sync>>(io::Error::from(ErrorKind::TimedOut).into())
+        };
+
+        assert!(
+            retry_boxed_with_config::<Config, _, _, _, _>(future, |_| None)
+                .await
+                .is_err()
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(started.elapsed(), Duration::from_millis(10));
+    }
+}
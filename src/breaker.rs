@@ -0,0 +1,363 @@
+//! A circuit breaker driven by the outcomes of retry loops.
+use crate::{retry_with_defaults, Retryable};
+use std::fmt::{Debug, Display};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The state of a circuit breaker.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BreakerState {
+    /// Operations are attempted normally.
+    Closed,
+    /// Operations fail fast without being attempted.
+    Open,
+    /// A single trial operation is in progress, and other operations fail
+    /// fast until it completes.
+    HalfOpen,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum State {
+    Closed {
+        failures: u32,
+    },
+    /// An empty value means that the cooldown is too long to represent, so
+    /// the breaker stays open.
+    Open {
+        until: Option<Instant>,
+    },
+    HalfOpen,
+}
+
+/// A circuit breaker that opens after repeated failed retry loops.
+///
+/// The breaker starts closed. Each retry loop that ends in an error (whether
+/// because its retries were exhausted or because its policy stopped it)
+/// counts as a failure, and a successful loop resets the count. After
+/// `failure_threshold` consecutive failures, the breaker opens, and calls fail
+/// fast without attempting the operation. Once `cooldown` has passed, the next
+/// call is allowed through as a trial, with the breaker half-open: if the
+/// trial succeeds the breaker closes, and if it fails the breaker opens for
+/// another cooldown period. Only the trial can close or reopen a half-open
+/// breaker: loops that were allowed through while it was closed and finish
+/// later don't affect it.
+///
+/// Clones share the same state.
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    state: Arc<Mutex<State>>,
+    failure_threshold: u32,
+    cooldown: Duration,
+}
+
+impl CircuitBreaker {
+    /// Create a closed circuit breaker.
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State::Closed { failures: 0 })),
+            failure_threshold,
+            cooldown,
+        }
+    }
+
+    /// Return the current state of the breaker.
+    ///
+    /// An open breaker whose cooldown has passed will still be reported as
+    /// open until the next call is allowed through.
+    pub fn state(&self) -> BreakerState {
+        match *self.lock() {
+            State::Closed { .. } => BreakerState::Closed,
+            State::Open { .. } => BreakerState::Open,
+            State::HalfOpen => BreakerState::HalfOpen,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Return a permit for a call, or an empty value if the call should fail
+    /// fast.
+    fn try_acquire(&self) -> Option<Permit<'_>> {
+        let mut state = self.lock();
+
+        let trial = match *state {
+            State::Closed { .. } => false,
+            State::Open { until: Some(until) } if Instant::now() >= until => {
+                *state = State::HalfOpen;
+                true
+            }
+            State::Open { .. } | State::HalfOpen => return None,
+        };
+
+        Some(Permit {
+            breaker: Some(self),
+            trial,
+        })
+    }
+
+    fn record(&self, trial: bool, success: bool) {
+        let mut state = self.lock();
+
+        *state = match (*state, trial, success) {
+            (State::HalfOpen, true, true) | (State::Closed { .. }, false, true) => {
+                State::Closed { failures: 0 }
+            }
+            (State::Closed { failures }, false, false)
+                if failures.saturating_add(1) < self.failure_threshold =>
+            {
+                State::Closed {
+                    failures: failures + 1,
+                }
+            }
+            (State::HalfOpen, true, false) | (State::Closed { .. }, false, false) => State::Open {
+                until: Instant::now().checked_add(self.cooldown),
+            },
+            // A loop that was allowed through while the breaker was closed
+            // doesn't affect a breaker that has since opened.
+            (state, _, _) => state,
+        };
+    }
+
+    fn abandon(&self) {
+        let mut state = self.lock();
+
+        if let State::HalfOpen = *state {
+            *state = State::Open {
+                until: Some(Instant::now()),
+            };
+        }
+    }
+}
+
+/// Permission for a call to go ahead, which records its outcome.
+///
+/// A trial permit is handed out when the breaker becomes half-open, and it's
+/// the only permit that can close or reopen the breaker (or abandon the
+/// trial when it's dropped without being completed).
+struct Permit<'a> {
+    breaker: Option<&'a CircuitBreaker>,
+    trial: bool,
+}
+
+impl Permit<'_> {
+    fn complete(mut self, success: bool) {
+        if let Some(breaker) = self.breaker.take() {
+            breaker.record(self.trial, success);
+        }
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if let Some(breaker) = self.breaker.take() {
+            if self.trial {
+                breaker.abandon();
+            }
+        }
+    }
+}
+
+/// The error returned by `retry_future_breaker`.
+#[derive(Debug, Eq, PartialEq)]
+pub enum BreakerError<E> {
+    /// The breaker was open, and the operation was not attempted.
+    Open,
+    /// The retry loop failed with the given error.
+    Failed(E),
+}
+
+impl<E: Display> Display for BreakerError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Open => write!(f, "Circuit breaker is open"),
+            Self::Failed(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for BreakerError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Open => None,
+            Self::Failed(error) => Some(error),
+        }
+    }
+}
+
+/// Execute a future with retries, guarded by a circuit breaker.
+///
+/// The breaker is checked before the first attempt, and the outcome of the
+/// whole retry loop is recorded when it completes. If the future is dropped
+/// during a half-open trial, the trial is abandoned and the next call will be
+/// allowed through.
+pub fn retry_future_breaker<F, Fut, T, E>(
+    f: F,
+    breaker: &CircuitBreaker,
+) -> impl Future<Output = Result<T, BreakerError<E>>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let breaker = breaker.clone();
+
+    async move {
+        let Some(permit) = breaker.try_acquire() else {
+            return Err(BreakerError::Open);
+        };
+
+        let result = retry_with_defaults(f).await;
+        permit.complete(result.is_ok());
+
+        result.map_err(BreakerError::Failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::pin::{pin, Pin};
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use std::task::Poll;
+    use tryhard::RetryPolicy;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct Error;
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            1
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(10)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn state_transitions() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(5));
+        let attempts = AtomicU32::new(0);
+        let succeed = AtomicBool::new(false);
+
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+
+            if succeed.load(Ordering::SeqCst) {
+                Ok(())
+            } else {
+                Err(Error)
+            }
+        };
+
+        assert_eq!(
+            retry_future_breaker(future, &breaker).await,
+            Err(BreakerError::Failed(Error))
+        );
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert_eq!(
+            retry_future_breaker(future, &breaker).await,
+            Err(BreakerError::Failed(Error))
+        );
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+
+        assert_eq!(
+            retry_future_breaker(future, &breaker).await,
+            Err(BreakerError::Open)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+
+        // A failed trial opens the breaker again.
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert_eq!(
+            retry_future_breaker(future, &breaker).await,
+            Err(BreakerError::Failed(Error))
+        );
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert_eq!(attempts.load(Ordering::SeqCst), 6);
+
+        // A successful trial closes it.
+        tokio::time::advance(Duration::from_secs(5)).await;
+        succeed.store(true, Ordering::SeqCst);
+        assert_eq!(retry_future_breaker(future, &breaker).await, Ok(()));
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn abandoned_trial() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(5));
+        let future = || async { Err::<(), _>(Error) };
+
+        assert!(retry_future_breaker(future, &breaker).await.is_err());
+        tokio::time::advance(Duration::from_secs(5)).await;
+
+        {
+            let trial = pin!(retry_future_breaker(future, &breaker));
+            assert!(poll_once(trial).await.is_none());
+            assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        }
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        assert!(matches!(
+            retry_future_breaker(future, &breaker).await,
+            Err(BreakerError::Failed(Error))
+        ));
+    }
+
+    async fn poll_once<F: Future + Unpin>(mut future: F) -> Option<F::Output> {
+        std::future::poll_fn(|cx| {
+            Poll::Ready(match Pin::new(&mut future).poll(cx) {
+                Poll::Ready(value) => Some(value),
+                Poll::Pending => None,
+            })
+        })
+        .await
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn late_completion_during_trial() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(5));
+
+        // Loops that started while the breaker was closed.
+        let finished = breaker.try_acquire().unwrap();
+        let dropped = breaker.try_acquire().unwrap();
+
+        breaker.try_acquire().unwrap().complete(false);
+        tokio::time::advance(Duration::from_secs(5)).await;
+
+        let trial = breaker.try_acquire().unwrap();
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+
+        finished.complete(true);
+        drop(dropped);
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+
+        trial.complete(false);
+        assert_eq!(breaker.state(), BreakerState::Open);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn long_cooldown() {
+        let breaker = CircuitBreaker::new(1, Duration::MAX);
+        let future = || async { Err::<(), _>(Error) };
+
+        assert!(retry_future_breaker(future, &breaker).await.is_err());
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert_eq!(
+            retry_future_breaker(future, &breaker).await,
+            Err(BreakerError::Open)
+        );
+    }
+}
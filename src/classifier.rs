@@ -0,0 +1,142 @@
+//! Retry policies that are determined at runtime.
+use crate::{retry_with_policy, Retryable};
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::Arc;
+use tryhard::RetryPolicy;
+
+pub(crate) type Rule<E> = Arc<dyn Fn(&E) -> Option<RetryPolicy> + Send + Sync>;
+
+/// An ordered list of classification rules for an error type.
+///
+/// The rules are consulted in order, and the first one that returns a policy
+/// wins. If no rule matches, the default backoff is used. Cloning a
+/// classifier is cheap, since the rules are shared.
+pub struct Classifier<E> {
+    rules: Arc<[Rule<E>]>,
+}
+
+impl<E> Classifier<E> {
+    /// Create a classifier with no rules.
+    pub fn new() -> Self {
+        Self {
+            rules: Arc::new([]),
+        }
+    }
+
+    /// Add a rule that will be consulted after the existing ones.
+    pub fn with<C>(self, rule: C) -> Self
+    where
+        C: Fn(&E) -> Option<RetryPolicy> + Send + Sync + 'static,
+    {
+        let mut rules = self.rules.to_vec();
+        rules.push(Arc::new(rule));
+
+        Self {
+            rules: rules.into(),
+        }
+    }
+
+    /// Return the policy from the first matching rule, if any.
+    pub fn classify(&self, error: &E) -> Option<RetryPolicy> {
+        self.rules.iter().find_map(|rule| rule(error))
+    }
+}
+
+impl<E> Clone for Classifier<E> {
+    fn clone(&self) -> Self {
+        Self {
+            rules: self.rules.clone(),
+        }
+    }
+}
+
+impl<E> Default for Classifier<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Execute a future with retries where the policy for each error is
+/// determined by a classifier, instead of the error type's
+/// `custom_retry_policy`.
+///
+/// The remaining configuration (the maximum number of retries, the initial
+/// delay, and the log level) still comes from the error type.
+pub fn retry_future_classified<F, Fut, T, E>(
+    f: F,
+    classifier: &Classifier<E>,
+) -> impl Future<Output = Result<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let classifier = classifier.clone();
+
+    retry_with_policy(f, move |error: &E| classifier.classify(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct Error(u32);
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            5
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(1)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            Some(RetryPolicy::Break)
+        }
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let classifier = Classifier::new()
+            .with(|error: &Error| (error.0 == 0).then_some(RetryPolicy::Break))
+            .with(|error: &Error| (error.0 < 10).then_some(RetryPolicy::Delay(Duration::ZERO)))
+            .with(|_: &Error| Some(RetryPolicy::Break));
+
+        assert_eq!(classifier.classify(&Error(0)), Some(RetryPolicy::Break));
+        assert_eq!(
+            classifier.classify(&Error(5)),
+            Some(RetryPolicy::Delay(Duration::ZERO))
+        );
+        assert_eq!(classifier.classify(&Error(10)), Some(RetryPolicy::Break));
+        assert_eq!(Classifier::<Error>::new().classify(&Error(0)), None);
+    }
+
+    #[tokio::test]
+    async fn classified() {
+        let classifier =
+            Classifier::new().with(|error: &Error| (error.0 == 3).then_some(RetryPolicy::Break));
+        let attempts = AtomicU32::new(0);
+
+        let future = || async {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            let result: Result<(), Error> = Err(Error(attempt));
+
+            result
+        };
+
+        assert_eq!(
+            retry_future_classified(future, &classifier).await,
+            Err(Error(3))
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+}
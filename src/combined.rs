@@ -0,0 +1,210 @@
+//! Retry policies that combine several classifiers.
+use crate::classifier::Rule;
+use crate::policy::Decision;
+use crate::{retry_with_policy, Retryable};
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::Arc;
+use tryhard::RetryPolicy;
+
+/// The rule for resolving conflicts between the policies in a combinator.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CombineRule {
+    /// Stop if any policy is `Break`, and otherwise use the longest delay.
+    BreakWins,
+    /// Use the shortest delay, and only stop if every policy is `Break`.
+    ShortestDelay,
+    /// Use the longest delay, and only stop if every policy is `Break`.
+    LongestDelay,
+}
+
+/// A list of classification rules for an error type whose policies are all
+/// consulted and then combined.
+///
+/// This is useful for orthogonal concerns (for example one rule based on an
+/// HTTP status and another based on a business rule), where unlike with a
+/// `Classifier`, no rule should take precedence just because it comes first.
+/// Rules that don't return a policy are ignored, and if none of them return
+/// one, the default backoff is used. Cloning a combinator is cheap, since the
+/// rules are shared.
+pub struct PolicyCombinator<E> {
+    rules: Arc<[Rule<E>]>,
+    combine: CombineRule,
+}
+
+impl<E> PolicyCombinator<E> {
+    /// Create a combinator with no rules.
+    pub fn new(combine: CombineRule) -> Self {
+        Self {
+            rules: Arc::new([]),
+            combine,
+        }
+    }
+
+    /// Add a rule.
+    pub fn with<C>(self, rule: C) -> Self
+    where
+        C: Fn(&E) -> Option<RetryPolicy> + Send + Sync + 'static,
+    {
+        let mut rules = self.rules.to_vec();
+        rules.push(Arc::new(rule));
+
+        Self {
+            rules: rules.into(),
+            combine: self.combine,
+        }
+    }
+
+    /// Return the combination of the policies from every matching rule, if
+    /// any match.
+    pub fn classify(&self, error: &E) -> Option<RetryPolicy> {
+        let decisions = self
+            .rules
+            .iter()
+            .filter_map(|rule| rule(error).map(Decision::from));
+
+        decisions
+            .reduce(|left, right| match (self.combine, left, right) {
+                (CombineRule::BreakWins, Decision::Stop, _)
+                | (CombineRule::BreakWins, _, Decision::Stop) => Decision::Stop,
+                (_, Decision::Stop, other) | (_, other, Decision::Stop) => other,
+                (CombineRule::ShortestDelay, Decision::Retry(left), Decision::Retry(right)) => {
+                    Decision::Retry(left.min(right))
+                }
+                (_, Decision::Retry(left), Decision::Retry(right)) => {
+                    Decision::Retry(left.max(right))
+                }
+            })
+            .map(RetryPolicy::from)
+    }
+}
+
+impl<E> Clone for PolicyCombinator<E> {
+    fn clone(&self) -> Self {
+        Self {
+            rules: self.rules.clone(),
+            combine: self.combine,
+        }
+    }
+}
+
+/// Execute a future with retries where the policy for each error is
+/// determined by a combinator, instead of the error type's
+/// `custom_retry_policy`.
+///
+/// The remaining configuration still comes from the error type.
+pub fn retry_future_combined<F, Fut, T, E>(
+    f: F,
+    combinator: &PolicyCombinator<E>,
+) -> impl Future<Output = Result<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let combinator = combinator.clone();
+
+    retry_with_policy(f, move |error: &E| combinator.classify(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct Error(u32);
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            5
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(1)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            Some(RetryPolicy::Break)
+        }
+    }
+
+    fn combinator(combine: CombineRule) -> PolicyCombinator<Error> {
+        PolicyCombinator::new(combine)
+            .with(|error: &Error| (error.0 == 0).then_some(RetryPolicy::Break))
+            .with(|error: &Error| {
+                (error.0 < 10).then_some(RetryPolicy::Delay(Duration::from_secs(1)))
+            })
+            .with(|error: &Error| {
+                (error.0 < 5).then_some(RetryPolicy::Delay(Duration::from_secs(2)))
+            })
+    }
+
+    #[test]
+    fn break_wins() {
+        let combinator = combinator(CombineRule::BreakWins);
+
+        assert_eq!(combinator.classify(&Error(0)), Some(RetryPolicy::Break));
+        assert_eq!(
+            combinator.classify(&Error(1)),
+            Some(RetryPolicy::Delay(Duration::from_secs(2)))
+        );
+        assert_eq!(combinator.classify(&Error(10)), None);
+    }
+
+    #[test]
+    fn shortest_delay() {
+        let combinator = combinator(CombineRule::ShortestDelay);
+
+        assert_eq!(
+            combinator.classify(&Error(0)),
+            Some(RetryPolicy::Delay(Duration::from_secs(1)))
+        );
+        assert_eq!(
+            combinator.classify(&Error(1)),
+            Some(RetryPolicy::Delay(Duration::from_secs(1)))
+        );
+        assert_eq!(
+            PolicyCombinator::new(CombineRule::ShortestDelay)
+                .with(|_: &Error| Some(RetryPolicy::Break))
+                .classify(&Error(0)),
+            Some(RetryPolicy::Break)
+        );
+    }
+
+    #[test]
+    fn longest_delay() {
+        let combinator = combinator(CombineRule::LongestDelay);
+
+        assert_eq!(
+            combinator.classify(&Error(0)),
+            Some(RetryPolicy::Delay(Duration::from_secs(2)))
+        );
+        assert_eq!(
+            combinator.classify(&Error(5)),
+            Some(RetryPolicy::Delay(Duration::from_secs(1)))
+        );
+        assert_eq!(combinator.classify(&Error(10)), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn combined() {
+        let combinator = PolicyCombinator::new(CombineRule::BreakWins)
+            .with(|error: &Error| (error.0 == 3).then_some(RetryPolicy::Break))
+            .with(|_: &Error| Some(RetryPolicy::Delay(Duration::from_millis(10))));
+        let attempts = AtomicU32::new(0);
+
+        let future = || async { Err::<(), _>(Error(attempts.fetch_add(1, Ordering::SeqCst))) };
+
+        assert_eq!(
+            retry_future_combined(future, &combinator).await,
+            Err(Error(3))
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+}
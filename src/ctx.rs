@@ -0,0 +1,149 @@
+//! Passing user data to retry hooks.
+use crate::{effective_max_retries, trace_start, RetryDriver, RetryStep, Retryable};
+use std::fmt::Debug;
+use std::future::Future;
+use std::time::Duration;
+
+/// Hooks that are called with a user-provided context during a retry loop.
+///
+/// This is useful for metrics or tracing state that would otherwise have to
+/// be captured by several closures. All methods do nothing by default.
+pub trait RetryContext<E> {
+    /// Called after a failed attempt that will be retried after the given
+    /// delay.
+    fn on_retry(&mut self, _attempt: u32, _delay: Duration, _error: &E) {}
+
+    /// Called immediately before sleeping for the given delay.
+    fn on_sleep(&mut self, _delay: Duration) {}
+
+    /// Called after the failed attempt that ends the loop (either because the
+    /// retries are exhausted or because the policy stopped it).
+    fn on_exhausted(&mut self, _attempts: u32, _error: &E) {}
+}
+
+/// Execute a future with retries, calling the context's hooks along the way.
+///
+/// The retry behavior is the same as for `retry_future`.
+pub async fn retry_future_with_ctx<Ctx, F, Fut, T, E>(ctx: &mut Ctx, mut f: F) -> Result<T, E>
+where
+    Ctx: RetryContext<E>,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let mut driver = RetryDriver::new();
+
+    if let Some(error) = driver.not_attempted() {
+        return Err(error);
+    }
+
+    trace_start::<E>(effective_max_retries::<E>());
+
+    loop {
+        let step = driver.next_inspecting(f().await, |attempt, delay, error| match delay {
+            Some(delay) => ctx.on_retry(attempt, delay, error),
+            None => ctx.on_exhausted(attempt, error),
+        });
+
+        match step {
+            RetryStep::Done(result) => return result,
+            RetryStep::WaitThen(delay) => {
+                ctx.on_sleep(delay);
+                driver.wait(delay).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tryhard::RetryPolicy;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub enum Error {
+        Transient(u32),
+        Fatal,
+    }
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            match self {
+                Self::Transient(_) => None,
+                Self::Fatal => Some(RetryPolicy::Break),
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        retries: Vec<(u32, Duration, u32)>,
+        slept: Duration,
+        exhausted: Option<(u32, Error)>,
+    }
+
+    impl RetryContext<Error> for Recorder {
+        fn on_retry(&mut self, attempt: u32, delay: Duration, error: &Error) {
+            if let Error::Transient(value) = error {
+                self.retries.push((attempt, delay, *value));
+            }
+        }
+
+        fn on_sleep(&mut self, delay: Duration) {
+            self.slept += delay;
+        }
+
+        fn on_exhausted(&mut self, attempts: u32, error: &Error) {
+            let error = match error {
+                Error::Transient(value) => Error::Transient(*value),
+                Error::Fatal => Error::Fatal,
+            };
+
+            self.exhausted = Some((attempts, error));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ctx() {
+        let mut recorder = Recorder::default();
+        let attempts = AtomicU32::new(0);
+        let future =
+            || async { Err::<(), _>(Error::Transient(attempts.fetch_add(1, Ordering::SeqCst))) };
+
+        assert_eq!(
+            retry_future_with_ctx(&mut recorder, future).await,
+            Err(Error::Transient(3))
+        );
+        assert_eq!(
+            recorder.retries,
+            vec![
+                (1, Duration::from_millis(100), 0),
+                (2, Duration::from_millis(200), 1),
+                (3, Duration::from_millis(400), 2),
+            ]
+        );
+        assert_eq!(recorder.slept, Duration::from_millis(700));
+        assert_eq!(recorder.exhausted, Some((4, Error::Transient(3))));
+
+        let mut recorder = Recorder::default();
+        let result =
+            retry_future_with_ctx(&mut recorder, || async { Err::<(), _>(Error::Fatal) }).await;
+
+        assert_eq!(result, Err(Error::Fatal));
+        assert!(recorder.retries.is_empty());
+        assert_eq!(recorder.exhausted, Some((1, Error::Fatal)));
+    }
+}
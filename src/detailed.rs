@@ -0,0 +1,704 @@
+//! Self-describing failures for retry loops that give up.
+use crate::driver::Attempted;
+use crate::{effective_max_retries, trace_start_named, RetryDriver, Retryable};
+use log::log;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Debug, Display};
+use std::future::Future;
+use std::time::Duration;
+
+/// The reason that a retry loop stopped without succeeding.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StopReason {
+    /// The maximum number of retries was reached.
+    Exhausted,
+    /// The policy for the last error was `Break`.
+    Break,
+    /// Waiting for the next delay would have exceeded the maximum elapsed
+    /// time.
+    Deadline,
+    /// Retries were disabled (see `set_retries_enabled`).
+    Disabled,
+    /// The failure happened outside the error type's `retry_window`.
+    OutsideWindow,
+    /// A shared budget (such as a `RetrySession`'s) ran out of retries before
+    /// the error type's maximum was reached.
+    BudgetExhausted,
+    /// The operation wasn't attempted, and the error is the error type's
+    /// `on_not_attempted` error.
+    NotAttempted,
+}
+
+impl Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exhausted => write!(f, "retries exhausted"),
+            Self::Break => write!(f, "not retryable"),
+            Self::Deadline => write!(f, "deadline exceeded"),
+            Self::Disabled => write!(f, "retries disabled"),
+            Self::OutsideWindow => write!(f, "outside retry window"),
+            Self::BudgetExhausted => write!(f, "retry budget exhausted"),
+            Self::NotAttempted => write!(f, "not attempted"),
+        }
+    }
+}
+
+/// The failure of a retry loop that gave up.
+#[derive(Debug, Eq, PartialEq)]
+pub struct RetryFailure<E> {
+    /// The label of the operation (the error type name by default).
+    pub label: String,
+    /// The reported error (from the last attempt unless the error type's
+    /// `report_error` strategy chooses another).
+    pub error: E,
+    /// The number of attempts, the time spent waiting, and the last error.
+    pub stats: RetryStats,
+    /// The reason that the loop stopped.
+    pub reason: StopReason,
+    /// The `Debug` representations of the errors from the attempts before the
+    /// last one, oldest first (limited by the error type's
+    /// `max_recent_errors`).
+    pub recent_errors: Vec<String>,
+}
+
+impl<E: Display> Display for RetryFailure<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} gave up after {} attempts ({}): {}",
+            self.label, self.stats.attempts, self.reason, self.error
+        )
+    }
+}
+
+impl<E: Debug> RetryFailure<E> {
+    /// Return the failure as key-value pairs for exporting to a telemetry
+    /// backend.
+    ///
+    /// These are the `label` and the `reason`, followed by the attributes of
+    /// the stats (see `RetryStats::as_attributes`).
+    pub fn as_attributes(&self) -> Vec<(&'static str, String)> {
+        let mut attributes = vec![
+            ("label", self.label.clone()),
+            ("reason", self.reason.to_string()),
+        ];
+        attributes.extend(self.stats.as_attributes());
+        attributes
+    }
+}
+
+/// Statistics about a retry loop, which are available whether or not it
+/// succeeds.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RetryStats {
+    /// The total number of attempts, including the first.
+    pub attempts: u32,
+    /// The total time spent waiting between attempts.
+    pub total_delay: Duration,
+    /// The `Debug` representation of the error from the last failed attempt
+    /// (if any attempt failed).
+    pub last_error: Option<String>,
+}
+
+impl RetryStats {
+    /// Return the stats as key-value pairs for exporting to a telemetry
+    /// backend.
+    ///
+    /// The keys are `attempts`, `total_delay_ms`, and `last_error` (which is
+    /// omitted if no attempt failed), and the values are formatted as strings,
+    /// so that they can be mapped to attributes in any format without
+    /// depending on it.
+    pub fn as_attributes(&self) -> Vec<(&'static str, String)> {
+        let mut attributes = vec![
+            ("attempts", self.attempts.to_string()),
+            ("total_delay_ms", self.total_delay.as_millis().to_string()),
+        ];
+
+        if let Some(last_error) = &self.last_error {
+            attributes.push(("last_error", last_error.clone()));
+        }
+
+        attributes
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for RetryFailure<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// The strategy for choosing which error a failure reports.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ReportStrategy {
+    /// The error from the last attempt.
+    #[default]
+    Last,
+    /// The error from the first attempt.
+    First,
+    /// The most recent error with the most frequent key (see `error_key`),
+    /// where errors without a key are compared by their `Debug`
+    /// representations.
+    ///
+    /// This is useful for flapping failures, where the last error may not be
+    /// representative.
+    MostFrequent,
+}
+
+/// Execute a future with retries, returning a failure that describes the
+/// operation (and why the loop stopped) if the loop gives up.
+///
+/// The label identifies the operation in the failure and in a message that
+/// is logged (at the error type's log level) when the loop gives up, which
+/// is useful when many retry loops share an error type. If no label is
+/// provided, the error type name is used. The failure also includes the most
+/// recent of the earlier errors, which helps with debugging operations whose
+/// errors change between attempts, and the error that it reports is chosen
+/// by the error type's `report_error` strategy.
+pub fn retry_future_detailed<F, Fut, T, E>(
+    label: Option<&str>,
+    f: F,
+) -> impl Future<Output = Result<T, RetryFailure<E>>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let label = label.map_or_else(|| std::any::type_name::<E>().to_string(), str::to_string);
+    let future = run_detailed(label, None, f);
+
+    async { future.await.map(|(value, _)| value) }
+}
+
+/// Execute a future with retries, returning the stats for the loop along with
+/// the value if it succeeds, or in the failure if it gives up.
+///
+/// The failure is the same as for `retry_future_detailed` with no label.
+pub fn retry_future_with_stats<F, Fut, T, E>(
+    f: F,
+) -> impl Future<Output = Result<(T, RetryStats), RetryFailure<E>>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    run_detailed(std::any::type_name::<E>().to_string(), None, f)
+}
+
+/// Execute a future with retries, identifying the operation by name.
+///
+/// The name is used as the label in the failure (as for
+/// `retry_future_detailed`), prefixes every message that the loop logs, and
+/// is included as the `operation` field of the `tracing` event if the
+/// `tracing` feature is enabled. This is the recommended entry point when
+/// several operations share an error type and need to be told apart.
+pub fn retry_future_named<F, Fut, T, E>(
+    name: &'static str,
+    f: F,
+) -> impl Future<Output = Result<T, RetryFailure<E>>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let future = run_detailed(name.to_string(), Some(name), f);
+
+    async { future.await.map(|(value, _)| value) }
+}
+
+/// Run a retry loop, describing the operation with the given label if it
+/// fails, and prefixing the log messages with the name (if there is one).
+async fn run_detailed<F, Fut, T, E>(
+    label: String,
+    name: Option<&'static str>,
+    mut f: F,
+) -> Result<(T, RetryStats), RetryFailure<E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    // The driver is created here (at the first poll) so that the elapsed time
+    // is measured from the first attempt.
+    let mut driver = match name {
+        Some(name) => RetryDriver::new().named(name),
+        None => RetryDriver::new(),
+    };
+    let mut attempts = 0;
+    let mut total_delay = Duration::ZERO;
+    let mut recent_errors = VecDeque::new();
+    let mut reported = ReportedError::new(E::report_error());
+
+    let result = match driver.not_attempted() {
+        Some(error) => Err(error),
+        None => {
+            trace_start_named::<E>(effective_max_retries::<E>(), &label);
+
+            loop {
+                attempts += 1;
+                let result = f().await;
+
+                if let Err(error) = &result {
+                    record_error::<E>(&mut recent_errors, error);
+                }
+
+                match driver.next_retaining(result) {
+                    Attempted::Done(result) => break result,
+                    Attempted::Retry(delay, error) => {
+                        reported.record(error);
+                        total_delay = total_delay.saturating_add(delay);
+                        driver.wait(delay).await;
+                    }
+                }
+            }
+        }
+    };
+
+    // The last recorded error is the one from the last failed attempt.
+    let stats = RetryStats {
+        attempts,
+        total_delay,
+        last_error: recent_errors.back().cloned(),
+    };
+
+    let error = match result {
+        Ok(value) => return Ok((value, stats)),
+        Err(error) => error,
+    };
+
+    // The driver records the reason at every point where it stops on a
+    // failure, including when the operation isn't attempted.
+    let reason = driver
+        .stop_reason()
+        .expect("the driver records the reason for stopping");
+    // The error from the last attempt isn't one of the earlier errors.
+    recent_errors.pop_back();
+    let error = reported.finish(error);
+
+    // In log-once mode the retry handler already logs the give-up.
+    if let Some(level) = E::log_level().filter(|_| !E::log_once()) {
+        if E::log_error_value() {
+            log!(
+                level,
+                "Giving up on {} after {} attempts ({}); error: {:?}",
+                label,
+                attempts,
+                reason,
+                error
+            );
+        } else {
+            log!(
+                level,
+                "Giving up on {} after {} attempts ({})",
+                label,
+                attempts,
+                reason
+            );
+        }
+    }
+
+    Err(RetryFailure {
+        label,
+        error,
+        stats,
+        reason,
+        recent_errors: recent_errors.into(),
+    })
+}
+
+/// Record the `Debug` representation of an error, keeping one more than the
+/// error type's maximum number of recent errors (since the last one will be
+/// returned as the error itself).
+fn record_error<E: Retryable + Debug>(recent_errors: &mut VecDeque<String>, error: &E) {
+    let limit = E::max_recent_errors().saturating_add(1);
+
+    if recent_errors.len() == limit {
+        recent_errors.pop_front();
+    }
+
+    recent_errors.push_back(format!("{:?}", error));
+}
+
+/// The errors that are needed to choose the reported error.
+enum ReportedError<E> {
+    Last,
+    First(Option<E>),
+    /// The number of errors for each key, with the most recent error for
+    /// that key and the position of that error.
+    MostFrequent(HashMap<String, (usize, usize, E)>),
+}
+
+impl<E: Retryable + Debug> ReportedError<E> {
+    fn new(strategy: ReportStrategy) -> Self {
+        match strategy {
+            ReportStrategy::Last => Self::Last,
+            ReportStrategy::First => Self::First(None),
+            ReportStrategy::MostFrequent => Self::MostFrequent(HashMap::new()),
+        }
+    }
+
+    /// Record an error from an attempt before the last one.
+    fn record(&mut self, error: E) {
+        match self {
+            Self::Last => {}
+            Self::First(first) => {
+                first.get_or_insert(error);
+            }
+            Self::MostFrequent(counts) => record_frequent(counts, error),
+        }
+    }
+
+    /// Return the error to report, given the error from the last attempt.
+    fn finish(self, last: E) -> E {
+        match self {
+            Self::Last => last,
+            Self::First(first) => first.unwrap_or(last),
+            Self::MostFrequent(mut counts) => {
+                record_frequent(&mut counts, last);
+
+                counts
+                    .into_values()
+                    .max_by_key(|(count, position, _)| (*count, *position))
+                    .map(|(_, _, error)| error)
+                    .expect("at least one error has been recorded")
+            }
+        }
+    }
+}
+
+/// Count an error under its key, keeping it as the most recent error for that
+/// key.
+fn record_frequent<E: Retryable + Debug>(
+    counts: &mut HashMap<String, (usize, usize, E)>,
+    error: E,
+) {
+    let key = error.error_key().unwrap_or_else(|| format!("{:?}", error));
+    let position = counts.values().map(|(count, _, _)| count).sum();
+    let count = counts.get(&key).map_or(0, |(count, _, _)| *count);
+
+    counts.insert(key, (count + 1, position, error));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::SingleCallError;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tryhard::RetryPolicy;
+
+    #[derive(thiserror::Error, Debug, Eq, PartialEq)]
+    pub enum Error {
+        #[error("Transient")]
+        Transient,
+        #[error("Timeout {0}")]
+        Timeout(u32),
+        #[error("Fatal")]
+        Fatal,
+    }
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(10)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn max_recent_errors() -> usize {
+            2
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            match self {
+                Self::Transient | Self::Timeout(_) => None,
+                Self::Fatal => Some(RetryPolicy::Break),
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn labelled() {
+        let future = || async { Err::<(), _>(Error::Transient) };
+        let failure = retry_future_detailed(Some("fetch-user"), future)
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            failure,
+            RetryFailure {
+                label: "fetch-user".to_string(),
+                error: Error::Transient,
+                stats: RetryStats {
+                    attempts: 4,
+                    total_delay: Duration::from_millis(70),
+                    last_error: Some("Transient".to_string()),
+                },
+                reason: StopReason::Exhausted,
+                recent_errors: vec!["Transient".to_string(); 2],
+            }
+        );
+        assert_eq!(
+            failure.to_string(),
+            "fetch-user gave up after 4 attempts (retries exhausted): Transient"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn as_attributes() {
+        let attempts = AtomicU32::new(0);
+        let future =
+            || async { Err::<(), _>(Error::Timeout(attempts.fetch_add(1, Ordering::SeqCst))) };
+        let failure = retry_future_detailed(Some("fetch-user"), future)
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            failure.as_attributes(),
+            vec![
+                ("label", "fetch-user".to_string()),
+                ("reason", "retries exhausted".to_string()),
+                ("attempts", "4".to_string()),
+                ("total_delay_ms", "70".to_string()),
+                ("last_error", "Timeout(3)".to_string()),
+            ]
+        );
+
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                attempt if attempt < 2 => Err(Error::Timeout(attempt)),
+                attempt => Ok(attempt),
+            }
+        };
+        let (value, stats) = retry_future_with_stats(future).await.unwrap();
+
+        assert_eq!(value, 2);
+        assert_eq!(
+            stats.as_attributes(),
+            vec![
+                ("attempts", "3".to_string()),
+                ("total_delay_ms", "30".to_string()),
+                ("last_error", "Timeout(1)".to_string()),
+            ]
+        );
+
+        let (_, stats) = retry_future_with_stats(|| async { Ok::<_, Error>(()) })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            stats.as_attributes(),
+            vec![
+                ("attempts", "1".to_string()),
+                ("total_delay_ms", "0".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stats_with_report_error() {
+        // The stats always have the error from the last attempt.
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            Err::<(), _>(FirstError(
+                ["refused", "reset"][attempts.fetch_add(1, Ordering::SeqCst).min(1) as usize],
+            ))
+        };
+        let failure = retry_future_with_stats(future).await.unwrap_err();
+
+        assert_eq!(failure.error, FirstError("refused"));
+        assert_eq!(
+            failure.stats.last_error.as_deref(),
+            Some("FirstError(\"reset\")")
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn named() {
+        let future = || async { Err::<(), _>(Error::Fatal) };
+        let failure = retry_future_named("commit", future).await.unwrap_err();
+
+        assert_eq!(failure.label, "commit");
+        assert_eq!(failure.stats.attempts, 1);
+        assert_eq!(
+            failure.to_string(),
+            "commit gave up after 1 attempts (not retryable): Fatal"
+        );
+        assert_eq!(
+            retry_future_named("commit", || async { Ok::<_, Error>(1) }).await,
+            Ok(1)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn default_label() {
+        let future = || async { Err::<(), _>(Error::Fatal) };
+        let failure = retry_future_detailed(None, future).await.unwrap_err();
+
+        assert_eq!(failure.label, std::any::type_name::<Error>());
+        assert_eq!(failure.stats.attempts, 1);
+        assert_eq!(failure.reason, StopReason::Break);
+        assert_eq!(
+            retry_future_detailed(None, || async { Ok::<_, Error>(1) }).await,
+            Ok(1)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn not_attempted() {
+        let attempts = AtomicU32::new(0);
+        let failure = retry_future_detailed(None, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(SingleCallError::Failed)
+        })
+        .await
+        .unwrap_err();
+
+        assert_eq!(attempts.load(Ordering::SeqCst), 0);
+        assert_eq!(failure.error, SingleCallError::NotAttempted);
+        assert_eq!(
+            (failure.stats.attempts, failure.reason),
+            (0, StopReason::NotAttempted)
+        );
+        assert_eq!(
+            failure.to_string(),
+            format!(
+                "{} gave up after 0 attempts (not attempted): Not attempted",
+                std::any::type_name::<SingleCallError>()
+            )
+        );
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct FirstError(&'static str);
+
+    impl Retryable for FirstError {
+        fn max_retries() -> u32 {
+            4
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(10)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn report_error() -> ReportStrategy {
+            ReportStrategy::First
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct FrequentError(&'static str);
+
+    impl Retryable for FrequentError {
+        fn max_retries() -> u32 {
+            4
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(10)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn report_error() -> ReportStrategy {
+            ReportStrategy::MostFrequent
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn report_error() {
+        const MESSAGES: [&str; 5] = ["refused", "reset", "refused", "reset", "timeout"];
+
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            Err::<(), _>(FirstError(
+                MESSAGES[attempts.fetch_add(1, Ordering::SeqCst) as usize],
+            ))
+        };
+        let failure = retry_future_detailed(None, future).await.unwrap_err();
+        assert_eq!(failure.error, FirstError("refused"));
+        assert_eq!(failure.stats.attempts, 5);
+
+        // Ties go to the most recent error.
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            Err::<(), _>(FrequentError(
+                MESSAGES[attempts.fetch_add(1, Ordering::SeqCst) as usize],
+            ))
+        };
+        let failure = retry_future_detailed(None, future).await.unwrap_err();
+        assert_eq!(failure.error, FrequentError("reset"));
+
+        let future = || async { Err::<(), _>(Error::Timeout(0)) };
+        let failure = retry_future_detailed(None, future).await.unwrap_err();
+        assert_eq!(failure.error, Error::Timeout(0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn recent_errors() {
+        let attempts = AtomicU32::new(0);
+        let future =
+            || async { Err::<(), _>(Error::Timeout(attempts.fetch_add(1, Ordering::SeqCst))) };
+
+        let failure = retry_future_detailed(None, future).await.unwrap_err();
+        assert_eq!(failure.error, Error::Timeout(3));
+        assert_eq!(failure.recent_errors, vec!["Timeout(1)", "Timeout(2)"]);
+
+        let future = || async { Err::<(), _>(Error::Fatal) };
+        let failure = retry_future_detailed(None, future).await.unwrap_err();
+        assert!(failure.recent_errors.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stop_reasons() {
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err::<(), _>(Error::Transient)
+            } else {
+                Err(Error::Fatal)
+            }
+        };
+
+        let failure = retry_future_detailed(None, future).await.unwrap_err();
+        assert_eq!(
+            (failure.stats.attempts, failure.reason),
+            (3, StopReason::Break)
+        );
+
+        // The policy isn't consulted for the last attempt.
+        attempts.store(1, Ordering::SeqCst);
+        let failure = retry_future_detailed(None, || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 4 {
+                Err::<(), _>(Error::Transient)
+            } else {
+                Err(Error::Fatal)
+            }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(
+            (failure.stats.attempts, failure.reason),
+            (4, StopReason::Exhausted)
+        );
+    }
+}
@@ -0,0 +1,449 @@
+//! Retry decisions without built-in execution.
+use crate::{
+    effective_max_retries, not_attempted, record_operation, yield_before_retry, ErrorBackoff,
+    LogOnRetry, Retryable, StopReason,
+};
+use std::fmt::Debug;
+use std::time::Duration;
+use tokio::time::Instant;
+use tryhard::RetryPolicy;
+
+/// The next step for the caller of a `RetryDriver`.
+#[derive(Debug, Eq, PartialEq)]
+pub enum RetryStep<T, E> {
+    /// The operation is complete with this result.
+    Done(Result<T, E>),
+    /// The operation should be attempted again after this delay.
+    WaitThen(Duration),
+}
+
+/// The outcome of an attempt, keeping the error when it will be retried.
+pub(crate) enum Attempted<T, E> {
+    Done(Result<T, E>),
+    Retry(Duration, E),
+}
+
+impl<T, E> From<Attempted<T, E>> for RetryStep<T, E> {
+    fn from(attempted: Attempted<T, E>) -> Self {
+        match attempted {
+            Attempted::Done(result) => Self::Done(result),
+            Attempted::Retry(delay, _) => Self::WaitThen(delay),
+        }
+    }
+}
+
+/// A retry loop that makes decisions but leaves the execution to the caller.
+///
+/// The caller runs the operation, passes each result to `next`, and is
+/// responsible for waiting (or scheduling the next attempt) when asked to.
+/// This is useful for non-async code or custom schedulers, such as actor
+/// systems that re-invoke operations themselves. The retry configuration and
+/// logging are the same as for `retry_future`.
+///
+/// A driver tracks the state of a single operation, so a new one should be
+/// used for each operation. The elapsed time (for the maximum and the logs) is
+/// measured from when the driver is created, so it should be created just
+/// before the first attempt.
+pub struct RetryDriver<E> {
+    backoff: ErrorBackoff<E>,
+    on_retry: LogOnRetry,
+    attempt: u32,
+    max_retries: u32,
+    /// Whether the maximum number of retries was lowered by a budget.
+    budget_limited: bool,
+    reset_on_success: bool,
+    stop_reason: Option<StopReason>,
+}
+
+impl<E: Retryable + Debug> RetryDriver<E> {
+    /// Create a driver for an operation that hasn't been attempted yet.
+    pub fn new() -> Self {
+        Self {
+            backoff: E::new_backoff(),
+            on_retry: LogOnRetry::new::<E>(),
+            attempt: 0,
+            max_retries: effective_max_retries::<E>(),
+            budget_limited: false,
+            reset_on_success: false,
+            stop_reason: None,
+        }
+    }
+
+    /// Reset the retry count and the default delays after each success.
+    ///
+    /// This allows a single driver to be used for a long-lived loop of
+    /// operations (such as a reconciler), where each successful step starts
+    /// the retry schedule over for the next failure.
+    pub fn reset_on_success(self, reset_on_success: bool) -> Self {
+        Self {
+            reset_on_success,
+            ..self
+        }
+    }
+
+    /// Prefix the driver's log messages with the given operation name.
+    pub(crate) fn named(self, name: &'static str) -> Self {
+        Self {
+            on_retry: self.on_retry.with_name(name),
+            ..self
+        }
+    }
+
+    /// Return the error type's `on_not_attempted` error if the configuration
+    /// doesn't allow the operation to be attempted at all, stopping the loop
+    /// with `StopReason::NotAttempted`.
+    ///
+    /// This should be checked before the first attempt.
+    pub(crate) fn not_attempted(&mut self) -> Option<E> {
+        let error = not_attempted::<E>(E::max_retries());
+
+        if error.is_some() {
+            self.stop_reason = Some(StopReason::NotAttempted);
+        }
+
+        error
+    }
+
+    /// Return the step to take after an attempt with the given result.
+    ///
+    /// The error is dropped when the step is `WaitThen`.
+    pub fn next<T>(&mut self, result: Result<T, E>) -> RetryStep<T, E> {
+        self.step(result, Self::decide, |_, _, _| {}).into()
+    }
+
+    /// Return the outcome of an attempt with the given result, without
+    /// dropping the error if it will be retried.
+    pub(crate) fn next_retaining<T>(&mut self, result: Result<T, E>) -> Attempted<T, E> {
+        self.step(result, Self::decide, |_, _, _| {})
+    }
+
+    /// Return the next step, calling the given function after a failed attempt
+    /// with the number of that attempt (counting from one), the delay (if the
+    /// operation will be retried), and the error.
+    pub(crate) fn next_inspecting<T, I>(
+        &mut self,
+        result: Result<T, E>,
+        inspect: I,
+    ) -> RetryStep<T, E>
+    where
+        I: FnOnce(u32, Option<Duration>, &E),
+    {
+        self.step(result, Self::decide, inspect).into()
+    }
+
+    /// Wait for the given delay before the next attempt, and then give other
+    /// tasks a chance to run (see `before_retry`).
+    pub(crate) async fn wait(&self, delay: Duration) {
+        tokio::time::sleep(delay).await;
+        self.before_retry(delay).await;
+    }
+
+    /// Give other tasks a chance to run before the next attempt after the
+    /// given delay, consuming a unit of the task's cooperative budget if the
+    /// error type asks for it, and yielding to the executor if the delay is
+    /// zero (or the error type asks for it).
+    ///
+    /// This should be awaited after each delay, however it's waited for, with
+    /// the time that was actually waited.
+    pub(crate) async fn before_retry(&self, delay: Duration) {
+        if E::cooperate() {
+            tokio::task::consume_budget().await;
+        }
+
+        if yield_before_retry::<E>(delay) {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// Exclude a period from the elapsed time that is checked against the
+    /// maximum.
+    pub(crate) fn exclude_from_elapsed(&mut self, duration: Duration) {
+        self.backoff.exclude_from_elapsed(duration);
+    }
+
+    /// Limit the time available to the loop to the given maximum, measured from
+    /// the given instant (if this is earlier than the error type's deadline).
+    pub(crate) fn limit_elapsed(&mut self, started: Instant, max_elapsed: Duration) {
+        self.backoff.limit_elapsed(started, max_elapsed);
+    }
+
+    /// Limit the number of retries to the given maximum (if this is lower
+    /// than the error type's), so that running out of them stops the loop
+    /// with `StopReason::BudgetExhausted`.
+    pub(crate) fn limit_retries(&mut self, max_retries: u32) {
+        if max_retries < self.max_retries {
+            self.max_retries = max_retries;
+            self.backoff.set_max_retries(max_retries);
+            self.budget_limited = true;
+        }
+    }
+
+    /// Return the reason that the loop stopped after the last failed attempt
+    /// (or an empty value if it hasn't stopped on a failure).
+    pub(crate) fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason
+    }
+
+    /// Indicate whether the policy will be consulted if the next attempt fails.
+    pub(crate) fn retries_remaining(&self) -> bool {
+        self.attempt < self.max_retries
+    }
+
+    /// Return the next step using the given custom policy for the error.
+    pub(crate) fn next_with_policy<T>(
+        &mut self,
+        result: Result<T, E>,
+        custom: Option<RetryPolicy>,
+    ) -> RetryStep<T, E> {
+        self.step(
+            result,
+            |backoff, attempt, error| backoff.decide_for(attempt, error, custom),
+            |_, _, _| {},
+        )
+        .into()
+    }
+
+    /// Return the next step, stopping the loop for the given reason if the
+    /// attempt failed.
+    pub(crate) fn next_without_retry<T>(
+        &mut self,
+        result: Result<T, E>,
+        reason: StopReason,
+    ) -> RetryStep<T, E> {
+        self.step(result, |_, _, _| Err(reason), |_, _, _| {})
+            .into()
+    }
+
+    /// Return the delay before retrying the error, or the reason for stopping.
+    fn decide(
+        backoff: &mut ErrorBackoff<E>,
+        attempt: u32,
+        error: &E,
+    ) -> Result<Duration, StopReason> {
+        backoff.decide_for(attempt, error, error.custom_retry_policy())
+    }
+
+    fn step<T, P, I>(&mut self, result: Result<T, E>, policy: P, inspect: I) -> Attempted<T, E>
+    where
+        P: FnOnce(&mut ErrorBackoff<E>, u32, &E) -> Result<Duration, StopReason>,
+        I: FnOnce(u32, Option<Duration>, &E),
+    {
+        // Retries are counted when they're logged.
+        if self.attempt == 0 {
+            record_operation();
+        }
+
+        match result {
+            Ok(value) => {
+                self.stop_reason = None;
+
+                if self.reset_on_success {
+                    self.attempt = 0;
+                    self.backoff.reset();
+                }
+
+                Attempted::Done(Ok(value))
+            }
+            Err(error) => {
+                if !self.retries_remaining() {
+                    self.stop_reason = Some(if self.budget_limited {
+                        StopReason::BudgetExhausted
+                    } else {
+                        StopReason::Exhausted
+                    });
+                    self.on_retry.log_now(self.attempt, None, &error);
+                    inspect(self.attempt + 1, None, &error);
+
+                    return Attempted::Done(Err(error));
+                }
+
+                self.attempt += 1;
+
+                match policy(&mut self.backoff, self.attempt, &error) {
+                    Ok(delay) => {
+                        self.on_retry.log_now(self.attempt, Some(delay), &error);
+                        inspect(self.attempt, Some(delay), &error);
+
+                        Attempted::Retry(delay, error)
+                    }
+                    Err(reason) => {
+                        self.stop_reason = Some(reason);
+                        self.on_retry.log_now(self.attempt, None, &error);
+                        inspect(self.attempt, None, &error);
+
+                        Attempted::Done(Err(error))
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<E: Retryable + Debug> Default for RetryDriver<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestError;
+
+    type Error = TestError<100>;
+
+    #[test]
+    fn success() {
+        let mut driver = RetryDriver::new();
+
+        assert_eq!(
+            driver.next::<()>(Err(Error::Transient)),
+            RetryStep::WaitThen(Duration::from_millis(100))
+        );
+        assert_eq!(
+            driver.next::<()>(Err(Error::Transient)),
+            RetryStep::WaitThen(Duration::from_millis(200))
+        );
+        assert_eq!(driver.next(Ok("foo")), RetryStep::Done(Ok("foo")));
+    }
+
+    #[test]
+    fn exhausted() {
+        let mut driver = RetryDriver::default();
+
+        for delay in [100, 200, 400] {
+            assert_eq!(
+                driver.next::<()>(Err(Error::Transient)),
+                RetryStep::WaitThen(Duration::from_millis(delay))
+            );
+        }
+
+        assert_eq!(
+            driver.next::<()>(Err(Error::Transient)),
+            RetryStep::Done(Err(Error::Transient))
+        );
+    }
+
+    #[test]
+    fn stop_reasons() {
+        let mut driver = RetryDriver::new();
+        driver.next::<()>(Err(Error::Fatal));
+        assert_eq!(driver.stop_reason(), Some(StopReason::Break));
+
+        let mut driver = RetryDriver::new();
+
+        for _ in 0..3 {
+            driver.next::<()>(Err(Error::Transient));
+            assert_eq!(driver.stop_reason(), None);
+        }
+
+        driver.next::<()>(Err(Error::Transient));
+        assert_eq!(driver.stop_reason(), Some(StopReason::Exhausted));
+
+        let mut driver = RetryDriver::new();
+        driver.limit_retries(1);
+        driver.next::<()>(Err(Error::Transient));
+        driver.next::<()>(Err(Error::Transient));
+        assert_eq!(driver.stop_reason(), Some(StopReason::BudgetExhausted));
+
+        let mut driver = RetryDriver::new();
+        driver.next_without_retry::<()>(Err(Error::Transient), StopReason::BudgetExhausted);
+        assert_eq!(driver.stop_reason(), Some(StopReason::BudgetExhausted));
+    }
+
+    #[test]
+    fn broken() {
+        let mut driver = RetryDriver::new();
+
+        assert_eq!(
+            driver.next::<()>(Err(Error::Transient)),
+            RetryStep::WaitThen(Duration::from_millis(100))
+        );
+        assert_eq!(
+            driver.next::<()>(Err(Error::Fatal)),
+            RetryStep::Done(Err(Error::Fatal))
+        );
+    }
+
+    #[test]
+    fn reset_on_success() {
+        let mut driver = RetryDriver::new().reset_on_success(true);
+
+        for _ in 0..2 {
+            for delay in [100, 200] {
+                assert_eq!(
+                    driver.next::<()>(Err(Error::Transient)),
+                    RetryStep::WaitThen(Duration::from_millis(delay))
+                );
+            }
+
+            assert_eq!(driver.next(Ok(())), RetryStep::Done(Ok(())));
+        }
+
+        // Without resetting, the schedule continues after a success.
+        let mut driver = RetryDriver::new();
+        driver.next::<()>(Err(Error::Transient));
+        driver.next(Ok(()));
+
+        assert_eq!(
+            driver.next::<()>(Err(Error::Transient)),
+            RetryStep::WaitThen(Duration::from_millis(200))
+        );
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct LimitedError;
+
+    impl Retryable for LimitedError {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn max_elapsed() -> Option<Duration> {
+            Some(Duration::from_secs(1))
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reset_on_success_restarts_elapsed() {
+        let mut driver = RetryDriver::new().reset_on_success(true);
+
+        // The successes are more than the maximum elapsed time apart, but each
+        // failure is retried, since the elapsed time restarts at each success.
+        for _ in 0..3 {
+            assert_eq!(
+                driver.next::<()>(Err(LimitedError)),
+                RetryStep::WaitThen(Duration::from_millis(100))
+            );
+
+            tokio::time::sleep(Duration::from_millis(600)).await;
+            assert_eq!(driver.next(Ok(())), RetryStep::Done(Ok(())));
+        }
+
+        // Without resetting, the third failure is past the deadline.
+        let mut driver = RetryDriver::new();
+
+        for _ in 0..2 {
+            driver.next::<()>(Err(LimitedError));
+            tokio::time::sleep(Duration::from_millis(600)).await;
+            driver.next(Ok(()));
+        }
+
+        assert_eq!(
+            driver.next::<()>(Err(LimitedError)),
+            RetryStep::Done(Err(LimitedError))
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Exercising retry logic without waiting.
+use crate::{effective_max_retries, trace_start, RetryDriver, RetryStep, Retryable};
+use std::fmt::Debug;
+use std::future::Future;
+use std::time::Duration;
+
+/// Execute a future with retries, skipping every delay.
+///
+/// This is intended for testing only: the attempts, the retry decisions, and
+/// the logging are the same as for `retry_future`, but the next attempt
+/// starts immediately (after yielding to the executor) instead of after the
+/// delay. Unlike pausing the Tokio
+/// clock, this doesn't need a specially configured runtime, so a test can
+/// check that an operation was retried a given number of times without
+/// taking any real time.
+///
+/// ```
+/// use retryable_error::{retry_future_dry_run, SimpleRetryable};
+/// use std::sync::atomic::{AtomicU32, Ordering};
+///
+/// #[derive(Debug)]
+/// struct Error;
+///
+/// impl SimpleRetryable for Error {
+///     const MAX_RETRIES: u32 = 3;
+///     const INITIAL_DELAY_MS: u64 = 60_000;
+/// }
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let attempts = AtomicU32::new(0);
+/// let result = retry_future_dry_run(|| async {
+///     attempts.fetch_add(1, Ordering::SeqCst);
+///     Err::<(), _>(Error)
+/// })
+/// .await;
+///
+/// assert!(result.is_err());
+/// assert_eq!(attempts.load(Ordering::SeqCst), 4);
+/// # });
+/// ```
+pub async fn retry_future_dry_run<F, Fut, T, E>(mut f: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let mut driver = RetryDriver::new();
+
+    if let Some(error) = driver.not_attempted() {
+        return Err(error);
+    }
+
+    trace_start::<E>(effective_max_retries::<E>());
+
+    loop {
+        match driver.next(f().await) {
+            RetryStep::Done(result) => return result,
+            RetryStep::WaitThen(_) => {
+                driver.before_retry(Duration::ZERO).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::{Duration, Instant};
+    use tryhard::RetryPolicy;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub enum Error {
+        Transient,
+        Fatal,
+    }
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            4
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_secs(60)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            match self {
+                Self::Transient => None,
+                Self::Fatal => Some(RetryPolicy::Break),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn dry_run() {
+        let attempts = AtomicU32::new(0);
+        let started = Instant::now();
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(Error::Transient)
+        };
+
+        assert_eq!(retry_future_dry_run(future).await, Err(Error::Transient));
+        assert_eq!(attempts.load(Ordering::SeqCst), 5);
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        let future = || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 6 {
+                Err::<(), _>(Error::Transient)
+            } else {
+                Err(Error::Fatal)
+            }
+        };
+
+        assert_eq!(retry_future_dry_run(future).await, Err(Error::Fatal));
+        assert_eq!(attempts.load(Ordering::SeqCst), 7);
+    }
+}
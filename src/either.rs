@@ -0,0 +1,302 @@
+//! `Retryable` support for `Either` error unions.
+use crate::forward::forward_error_options;
+use crate::Retryable;
+use ::either::Either;
+use log::Level;
+use std::time::Duration;
+
+/// An `Either` of two `Retryable` error types is `Retryable`.
+///
+/// The per-error options (such as the policy, the seed delay, and the key) for
+/// an error value come from whichever side holds it, and so does
+/// `default_is_break`, which only applies to that side's errors.
+///
+/// The static configuration can't depend on the error value, so it combines
+/// the configuration of both sides:
+///
+/// - The larger value is used for the number of retries, the initial,
+///   minimum, and next delays, the multiplier, the delay quantum, and the
+///   number of recent errors and history records.
+/// - The log level is the more severe of the two (so that neither side's
+///   messages are logged below the level it asks for), and none is only used
+///   if both sides disable logging.
+/// - The first attempt is only counted if both sides count it, and error
+///   values are only logged if both sides allow it.
+/// - The maximum elapsed time and maximum delay are the larger of the two,
+///   and are only limited if both sides limit them.
+/// - Yielding, cooperating, logging elapsed time, logging once, and fitting
+///   the delays to the maximum elapsed time are enabled if either side enables
+///   them.
+/// - The jitter strategy and seed, retry window, escalating log levels, and
+///   error for operations that aren't attempted come from the left side if
+///   it has them (and otherwise the right), and the strategy for reporting
+///   errors always comes from the left side.
+impl<L: Retryable, R: Retryable> Retryable for Either<L, R> {
+    fn max_retries() -> u32 {
+        L::max_retries().max(R::max_retries())
+    }
+
+    fn count_first_attempt() -> bool {
+        L::count_first_attempt() && R::count_first_attempt()
+    }
+
+    fn on_not_attempted() -> Option<Self> {
+        L::on_not_attempted()
+            .map(Either::Left)
+            .or_else(|| R::on_not_attempted().map(Either::Right))
+    }
+
+    fn default_initial_delay() -> Duration {
+        L::default_initial_delay().max(R::default_initial_delay())
+    }
+
+    fn log_level() -> Option<Level> {
+        // More severe levels compare as smaller.
+        match (L::log_level(), R::log_level()) {
+            (Some(l), Some(r)) => Some(l.min(r)),
+            (l, r) => l.or(r),
+        }
+    }
+
+    fn escalating_log_levels() -> Option<&'static [Level]> {
+        L::escalating_log_levels().or_else(R::escalating_log_levels)
+    }
+
+    fn log_elapsed() -> bool {
+        L::log_elapsed() || R::log_elapsed()
+    }
+
+    fn log_once() -> bool {
+        L::log_once() || R::log_once()
+    }
+
+    fn log_error_value() -> bool {
+        L::log_error_value() && R::log_error_value()
+    }
+
+    fn max_recent_errors() -> usize {
+        L::max_recent_errors().max(R::max_recent_errors())
+    }
+
+    fn max_history() -> usize {
+        L::max_history().max(R::max_history())
+    }
+
+    fn report_error() -> crate::ReportStrategy {
+        L::report_error()
+    }
+
+    fn max_elapsed() -> Option<Duration> {
+        L::max_elapsed()
+            .zip(R::max_elapsed())
+            .map(|(l, r)| l.max(r))
+    }
+
+    fn fit_to_max_elapsed() -> bool {
+        L::fit_to_max_elapsed() || R::fit_to_max_elapsed()
+    }
+
+    fn backoff_multiplier() -> f64 {
+        L::backoff_multiplier().max(R::backoff_multiplier())
+    }
+
+    fn next_delay(attempt: u32, previous: Duration) -> Duration {
+        L::next_delay(attempt, previous).max(R::next_delay(attempt, previous))
+    }
+
+    fn min_delay() -> Option<Duration> {
+        L::min_delay().max(R::min_delay())
+    }
+
+    fn max_delay() -> Option<Duration> {
+        L::max_delay().zip(R::max_delay()).map(|(l, r)| l.max(r))
+    }
+
+    fn quantize_delay() -> Option<Duration> {
+        L::quantize_delay().max(R::quantize_delay())
+    }
+
+    fn retry_window() -> Option<crate::RetryWindow> {
+        L::retry_window().or_else(R::retry_window)
+    }
+
+    #[cfg(feature = "jitter")]
+    fn jitter() -> Option<crate::Jitter> {
+        L::jitter().or_else(R::jitter)
+    }
+
+    #[cfg(feature = "jitter")]
+    fn jitter_seed() -> Option<u64> {
+        L::jitter_seed().or_else(R::jitter_seed)
+    }
+
+    fn yield_between_attempts() -> bool {
+        L::yield_between_attempts() || R::yield_between_attempts()
+    }
+
+    fn cooperate() -> bool {
+        L::cooperate() || R::cooperate()
+    }
+
+    forward_error_options!(match self {
+        Either::Left(error) => error,
+        Either::Right(error) => error,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retry_future;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tryhard::RetryPolicy;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct Left;
+
+    impl Retryable for Left {
+        fn max_retries() -> u32 {
+            2
+        }
+
+        fn log_level() -> Option<Level> {
+            Some(Level::Warn)
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(1)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct Right;
+
+    impl Retryable for Right {
+        fn max_retries() -> u32 {
+            4
+        }
+
+        fn log_level() -> Option<Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::ZERO
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            Some(RetryPolicy::Break)
+        }
+    }
+
+    type Error = Either<Left, Right>;
+
+    #[test]
+    fn combined_config() {
+        assert_eq!(Error::max_retries(), 4);
+        assert_eq!(Error::default_initial_delay(), Duration::from_millis(1));
+        assert_eq!(Error::log_level(), Some(Level::Warn));
+        assert_eq!(Either::<Verbose, Left>::log_level(), Some(Level::Warn));
+        assert_eq!(Either::<Verbose, Right>::log_level(), Some(Level::Debug));
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct Verbose;
+
+    impl Retryable for Verbose {
+        fn max_retries() -> u32 {
+            1
+        }
+
+        fn log_level() -> Option<Level> {
+            Some(Level::Debug)
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(1)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn left() {
+        let attempts = AtomicU32::new(0);
+
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            let result: Result<(), Error> = Err(Either::Left(Left));
+
+            result
+        };
+
+        assert_eq!(retry_future(future).await, Err(Either::Left(Left)));
+        assert_eq!(attempts.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn right() {
+        let attempts = AtomicU32::new(0);
+
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            let result: Result<(), Error> = Err(Either::Right(Right));
+
+            result
+        };
+
+        assert_eq!(retry_future(future).await, Err(Either::Right(Right)));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct Strict;
+
+    impl Retryable for Strict {
+        fn max_retries() -> u32 {
+            4
+        }
+
+        fn log_level() -> Option<Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(1)
+        }
+
+        fn default_is_break() -> bool {
+            true
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn default_is_break_per_side() {
+        let attempts = AtomicU32::new(0);
+
+        let future = || async {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            let result: Result<(), Either<Left, Strict>> = if attempt < 2 {
+                Err(Either::Left(Left))
+            } else {
+                Err(Either::Right(Strict))
+            };
+
+            result
+        };
+
+        // The left side's errors still get the default delays.
+        assert_eq!(retry_future(future).await, Err(Either::Right(Strict)));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+}
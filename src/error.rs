@@ -0,0 +1,202 @@
+//! A shared error type that carries its own retry policy.
+use crate::Retryable;
+use std::fmt::{Debug, Display};
+use std::io;
+use std::time::Duration;
+use tryhard::RetryPolicy;
+
+/// An error that was classified when it was created.
+///
+/// This is an alternative to implementing `Retryable` for many small error
+/// types: they can be converted into a `RetryableError` (for example by `?`
+/// with a `From` impl), which centralizes the retry policy at the conversion
+/// sites. The retry configuration is fixed: three retries with a default
+/// initial delay of 100 milliseconds, logged at the `Warn` level.
+///
+/// ```
+/// use retryable_error::RetryableError;
+/// use std::time::Duration;
+///
+/// #[derive(Debug)]
+/// enum LookupError {
+///     Throttled,
+///     Missing,
+/// }
+///
+/// impl std::fmt::Display for LookupError {
+///     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+///         write!(f, "{:?}", self)
+///     }
+/// }
+///
+/// impl std::error::Error for LookupError {}
+///
+/// impl From<LookupError> for RetryableError {
+///     fn from(error: LookupError) -> Self {
+///         match error {
+///             LookupError::Throttled => Self::delay(error, Duration::from_secs(1)),
+///             LookupError::Missing => Self::permanent(error),
+///         }
+///     }
+/// }
+///
+/// fn lookup() -> Result<(), RetryableError> {
+///     Err(LookupError::Missing)?
+/// }
+///
+/// assert!(lookup().unwrap_err().is_permanent());
+/// ```
+pub struct RetryableError {
+    policy: Option<RetryPolicy>,
+    source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl RetryableError {
+    /// Create an error with the given custom policy (where an empty value
+    /// represents the default).
+    pub fn new<E>(policy: Option<RetryPolicy>, source: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Self {
+            policy,
+            source: source.into(),
+        }
+    }
+
+    /// Create an error that is retried with the default backoff.
+    pub fn transient<E>(source: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Self::new(None, source)
+    }
+
+    /// Create an error that is retried after the given delay.
+    pub fn delay<E>(source: E, delay: Duration) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Self::new(Some(RetryPolicy::Delay(delay)), source)
+    }
+
+    /// Create an error that isn't retried.
+    pub fn permanent<E>(source: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Self::new(Some(RetryPolicy::Break), source)
+    }
+
+    /// Indicate whether the error stops the retry loop.
+    pub fn is_permanent(&self) -> bool {
+        matches!(self.policy, Some(RetryPolicy::Break))
+    }
+
+    /// Return the underlying error.
+    pub fn into_inner(self) -> Box<dyn std::error::Error + Send + Sync> {
+        self.source
+    }
+}
+
+impl Debug for RetryableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.source, f)
+    }
+}
+
+impl Display for RetryableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.source, f)
+    }
+}
+
+impl std::error::Error for RetryableError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// I/O errors that may be resolved by trying again (such as timeouts and
+/// dropped connections) are transient, and all others are permanent.
+impl From<io::Error> for RetryableError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::Interrupted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof => Self::transient(error),
+            _ => Self::permanent(error),
+        }
+    }
+}
+
+impl Retryable for RetryableError {
+    fn max_retries() -> u32 {
+        3
+    }
+
+    fn default_initial_delay() -> Duration {
+        Duration::from_millis(100)
+    }
+
+    fn log_level() -> Option<log::Level> {
+        Some(log::Level::Warn)
+    }
+
+    fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+        self.policy.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retry_future;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    async fn read(attempts: &AtomicU32) -> Result<u32, RetryableError> {
+        let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+
+        match attempt {
+            0 => Err(io::Error::from(io::ErrorKind::TimedOut))?,
+            1 => Err(io::Error::from(io::ErrorKind::ConnectionReset))?,
+            2 => Ok(attempt),
+            _ => Err(io::Error::from(io::ErrorKind::PermissionDenied))?,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn io_errors() {
+        let attempts = AtomicU32::new(0);
+        assert_eq!(retry_future(|| read(&attempts)).await.unwrap(), 2);
+
+        let error = retry_future(|| read(&attempts)).await.unwrap_err();
+        assert!(error.is_permanent());
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+        assert_eq!(
+            error.into_inner().downcast::<io::Error>().unwrap().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+    }
+
+    #[test]
+    fn policies() {
+        let error = RetryableError::delay("throttled", Duration::from_secs(1));
+
+        assert_eq!(
+            error.custom_retry_policy(),
+            Some(RetryPolicy::Delay(Duration::from_secs(1)))
+        );
+        assert_eq!(error.to_string(), "throttled");
+        assert_eq!(
+            RetryableError::transient("flaky").custom_retry_policy(),
+            None
+        );
+        assert!(!RetryableError::transient("flaky").is_permanent());
+    }
+}
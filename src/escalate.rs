@@ -0,0 +1,110 @@
+//! Switching to a fallback operation partway through a retry loop.
+use crate::{retry_with_defaults, Retryable};
+use std::fmt::Debug;
+use std::future::Future;
+
+/// Execute a future with retries, switching from the primary operation to the
+/// secondary one after the given number of attempts.
+///
+/// This models failover within a single retry loop (for example trying the
+/// primary endpoint three times before moving to a secondary endpoint): the
+/// first `switch_after` attempts use `primary`, and the remaining attempts
+/// use `secondary`. Both share the same backoff and retry budget, which come
+/// from the error type as for `retry_future`.
+pub fn retry_future_escalate<P, PFut, S, SFut, T, E>(
+    mut primary: P,
+    mut secondary: S,
+    switch_after: u32,
+) -> impl Future<Output = Result<T, E>>
+where
+    P: FnMut() -> PFut,
+    PFut: Future<Output = Result<T, E>>,
+    S: FnMut() -> SFut,
+    SFut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let mut attempts = 0;
+
+    retry_with_defaults(move || {
+        let attempt = if attempts < switch_after {
+            Attempt::Primary(primary())
+        } else {
+            Attempt::Secondary(secondary())
+        };
+        attempts += 1;
+
+        async move {
+            match attempt {
+                Attempt::Primary(future) => future.await,
+                Attempt::Secondary(future) => future.await,
+            }
+        }
+    })
+}
+
+enum Attempt<P, S> {
+    Primary(P),
+    Secondary(S),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+    use tryhard::RetryPolicy;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct Error(&'static str);
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            5
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(10)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn escalate() {
+        let attempts = AtomicU32::new(0);
+        let primary_attempts = AtomicU32::new(0);
+        let primary = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            primary_attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<&str, _>(Error("primary"))
+        };
+        let secondary = || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 4 {
+                Err(Error("secondary"))
+            } else {
+                Ok("secondary")
+            }
+        };
+
+        assert_eq!(
+            retry_future_escalate(primary, secondary, 3).await,
+            Ok("secondary")
+        );
+        assert_eq!(primary_attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(attempts.load(Ordering::SeqCst), 5);
+
+        let result = retry_future_escalate(
+            || async { Err::<(), _>(Error("primary")) },
+            || async { Err(Error("secondary")) },
+            10,
+        )
+        .await;
+        assert_eq!(result, Err(Error("primary")));
+    }
+}
@@ -0,0 +1,64 @@
+//! Fluent syntax for retrying operations.
+use crate::{retry_future, Retryable};
+use std::fmt::Debug;
+use std::future::Future;
+
+/// An extension trait that allows an operation to be retried with method
+/// syntax.
+///
+/// `operation.retry()` is equivalent to `retry_future(operation)`, and it's
+/// implemented for every closure (or function) with the same bounds. This
+/// trait is sealed, so it can't be implemented outside this crate.
+///
+/// ```
+/// use retryable_error::{RetryableFutureExt, SimpleRetryable};
+///
+/// #[derive(Debug)]
+/// struct Error;
+///
+/// impl SimpleRetryable for Error {
+///     const MAX_RETRIES: u32 = 3;
+///     const INITIAL_DELAY_MS: u64 = 1;
+/// }
+///
+/// async fn fetch() -> Result<u32, Error> {
+///     Ok(1)
+/// }
+///
+/// # let runtime = tokio::runtime::Builder::new_current_thread()
+/// #     .enable_time()
+/// #     .build()
+/// #     .unwrap();
+/// # runtime.block_on(async {
+/// assert_eq!(fetch.retry().await.unwrap(), 1);
+/// assert!((|| async { Err::<(), _>(Error) }).retry().await.is_err());
+/// # });
+/// ```
+pub trait RetryableFutureExt<T, E>: sealed::Sealed {
+    /// Execute the operation with retries (see `retry_future`).
+    fn retry(self) -> impl Future<Output = Result<T, E>>;
+}
+
+impl<F, Fut, T, E> RetryableFutureExt<T, E> for F
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    fn retry(self) -> impl Future<Output = Result<T, E>> {
+        retry_future(self)
+    }
+}
+
+mod sealed {
+    use std::future::Future;
+
+    pub trait Sealed {}
+
+    impl<F, Fut> Sealed for F
+    where
+        F: FnMut() -> Fut,
+        Fut: Future,
+    {
+    }
+}
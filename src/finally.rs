@@ -0,0 +1,124 @@
+//! Cleaning up after retry loops.
+use crate::{retry_with_defaults, Retryable};
+use std::fmt::Debug;
+use std::future::Future;
+
+/// Execute a future with retries, running a cleanup future once the loop has
+/// finished for any reason.
+///
+/// The cleanup runs exactly once, after a success, after the policy stops the
+/// loop, or after the retries are exhausted, and the result is returned once
+/// it has completed. If the future is dropped after it has started but before
+/// the loop finishes, the cleanup is spawned on the current Tokio runtime
+/// instead (if there is no runtime, it can't be run, and it is dropped). A
+/// future that is dropped without ever being polled makes no attempts, and
+/// doesn't run the cleanup.
+pub async fn retry_future_finally<F, Fut, T, E, C, CFut>(f: F, cleanup: C) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+    C: FnOnce() -> CFut + Send + 'static,
+    CFut: Future<Output = ()> + Send + 'static,
+{
+    let mut guard = CleanupGuard {
+        cleanup: Some(cleanup),
+    };
+    let result = retry_with_defaults(f).await;
+
+    if let Some(cleanup) = guard.cleanup.take() {
+        cleanup().await;
+    }
+
+    result
+}
+
+/// Spawns the cleanup if it hasn't been run when dropped.
+struct CleanupGuard<C, CFut>
+where
+    C: FnOnce() -> CFut + Send + 'static,
+    CFut: Future<Output = ()> + Send + 'static,
+{
+    cleanup: Option<C>,
+}
+
+impl<C, CFut> Drop for CleanupGuard<C, CFut>
+where
+    C: FnOnce() -> CFut + Send + 'static,
+    CFut: Future<Output = ()> + Send + 'static,
+{
+    fn drop(&mut self) {
+        // This is a no-op unless the future was dropped before the loop
+        // finished.
+        if let Some(cleanup) = self.cleanup.take() {
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                runtime.spawn(cleanup());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestError;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    type Error = TestError<10>;
+
+    fn counting_cleanup(cleanups: &Arc<AtomicU32>) -> impl FnOnce() -> std::future::Ready<()> {
+        let cleanups = cleanups.clone();
+
+        move || {
+            cleanups.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cleanup_after_completion() {
+        let cleanups = Arc::new(AtomicU32::new(0));
+
+        let result =
+            retry_future_finally(|| async { Ok::<_, Error>(1) }, counting_cleanup(&cleanups)).await;
+        assert_eq!(result, Ok(1));
+        assert_eq!(cleanups.load(Ordering::SeqCst), 1);
+
+        let result = retry_future_finally(
+            || async { Err::<(), _>(Error::Fatal) },
+            counting_cleanup(&cleanups),
+        )
+        .await;
+        assert_eq!(result, Err(Error::Fatal));
+        assert_eq!(cleanups.load(Ordering::SeqCst), 2);
+
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(Error::Transient)
+        };
+        let result = retry_future_finally(future, counting_cleanup(&cleanups)).await;
+        assert_eq!(result, Err(Error::Transient));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+        assert_eq!(cleanups.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cleanup_after_cancellation() {
+        let cleanups = Arc::new(AtomicU32::new(0));
+        let future = retry_future_finally(
+            || async { Err::<(), _>(Error::Transient) },
+            counting_cleanup(&cleanups),
+        );
+
+        // The loop is dropped while waiting before a retry.
+        assert!(tokio::time::timeout(Duration::from_millis(5), future)
+            .await
+            .is_err());
+        tokio::task::yield_now().await;
+
+        assert_eq!(cleanups.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,179 @@
+//! Forwarding the configuration of error types that wrap other error types.
+use crate::Retryable;
+use tryhard::RetryPolicy;
+
+/// Implement the static options of `Retryable` by forwarding them to the
+/// given underlying error type.
+///
+/// This covers every static option except for `on_not_attempted` (which has
+/// to build the wrapper) and `default_is_break` (which `forward_error_options`
+/// applies to the underlying errors only), so that the wrappers pick up new
+/// options without having to list them.
+macro_rules! forward_static_options {
+    ($inner:ty) => {
+        fn max_retries() -> u32 {
+            <$inner>::max_retries()
+        }
+
+        fn count_first_attempt() -> bool {
+            <$inner>::count_first_attempt()
+        }
+
+        fn default_initial_delay() -> std::time::Duration {
+            <$inner>::default_initial_delay()
+        }
+
+        fn log_level() -> Option<log::Level> {
+            <$inner>::log_level()
+        }
+
+        fn escalating_log_levels() -> Option<&'static [log::Level]> {
+            <$inner>::escalating_log_levels()
+        }
+
+        fn log_elapsed() -> bool {
+            <$inner>::log_elapsed()
+        }
+
+        fn log_once() -> bool {
+            <$inner>::log_once()
+        }
+
+        fn log_error_value() -> bool {
+            <$inner>::log_error_value()
+        }
+
+        fn max_recent_errors() -> usize {
+            <$inner>::max_recent_errors()
+        }
+
+        fn max_history() -> usize {
+            <$inner>::max_history()
+        }
+
+        fn report_error() -> crate::ReportStrategy {
+            <$inner>::report_error()
+        }
+
+        fn max_elapsed() -> Option<std::time::Duration> {
+            <$inner>::max_elapsed()
+        }
+
+        fn fit_to_max_elapsed() -> bool {
+            <$inner>::fit_to_max_elapsed()
+        }
+
+        fn backoff_multiplier() -> f64 {
+            <$inner>::backoff_multiplier()
+        }
+
+        fn next_delay(attempt: u32, previous: std::time::Duration) -> std::time::Duration {
+            <$inner>::next_delay(attempt, previous)
+        }
+
+        fn min_delay() -> Option<std::time::Duration> {
+            <$inner>::min_delay()
+        }
+
+        fn max_delay() -> Option<std::time::Duration> {
+            <$inner>::max_delay()
+        }
+
+        fn quantize_delay() -> Option<std::time::Duration> {
+            <$inner>::quantize_delay()
+        }
+
+        fn retry_window() -> Option<crate::RetryWindow> {
+            <$inner>::retry_window()
+        }
+
+        #[cfg(feature = "jitter")]
+        fn jitter() -> Option<crate::Jitter> {
+            <$inner>::jitter()
+        }
+
+        #[cfg(feature = "jitter")]
+        fn jitter_seed() -> Option<u64> {
+            <$inner>::jitter_seed()
+        }
+
+        fn yield_between_attempts() -> bool {
+            <$inner>::yield_between_attempts()
+        }
+
+        fn cooperate() -> bool {
+            <$inner>::cooperate()
+        }
+    };
+}
+
+/// Implement the per-error options of `Retryable` (including the required
+/// `custom_retry_policy`) by matching the wrapper against the given patterns,
+/// forwarding to the bound underlying error, or using the defaults for the
+/// patterns after the semicolon, which don't hold one.
+///
+/// The underlying error types' `default_is_break` is applied to their own
+/// errors, so that the wrapper's other errors still get the default backoff.
+macro_rules! forward_error_options {
+    (match $self:ident { $($pattern:pat => $error:ident),+ $(,)? $(; $($default:pat),+ $(,)?)? }) => {
+        fn custom_retry_policy(&$self) -> Option<tryhard::RetryPolicy> {
+            match $self {
+                $($pattern => crate::forward::policy_or_break($error),)+
+                $($($default)|+ => None,)?
+            }
+        }
+
+        fn max_delay_at(&$self, attempt: u32) -> Option<std::time::Duration> {
+            match $self {
+                $($pattern => $error.max_delay_at(attempt),)+
+                $($($default)|+ => Self::max_delay(),)?
+            }
+        }
+
+        fn seed_delay(&$self) -> Option<std::time::Duration> {
+            match $self {
+                $($pattern => $error.seed_delay(),)+
+                $($($default)|+ => None,)?
+            }
+        }
+
+        fn error_key(&$self) -> Option<String> {
+            match $self {
+                $($pattern => $error.error_key(),)+
+                $($($default)|+ => None,)?
+            }
+        }
+
+        fn policy_with_prev(&$self, prev: Option<&str>, attempt: u32) -> Option<tryhard::RetryPolicy> {
+            match $self {
+                $($pattern => $error.policy_with_prev(prev, attempt),)+
+                $($($default)|+ => None,)?
+            }
+        }
+
+        fn final_attempt_policy(&$self) -> Option<tryhard::RetryPolicy> {
+            match $self {
+                $($pattern => $error.final_attempt_policy(),)+
+                $($($default)|+ => None,)?
+            }
+        }
+
+        fn session_budget_key(&$self) -> Option<&'static str> {
+            match $self {
+                $($pattern => $error.session_budget_key(),)+
+                $($($default)|+ => None,)?
+            }
+        }
+    };
+}
+
+pub(crate) use forward_error_options;
+pub(crate) use forward_static_options;
+
+/// Return the custom policy for an error, or `Break` if it doesn't have one
+/// and its type breaks by default.
+pub(crate) fn policy_or_break<E: Retryable>(error: &E) -> Option<RetryPolicy> {
+    error
+        .custom_retry_policy()
+        .or_else(|| E::default_is_break().then_some(RetryPolicy::Break))
+}
@@ -0,0 +1,180 @@
+//! Observing the progress of a retry loop.
+use crate::{effective_max_retries, retry_with_defaults, Retryable};
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// A handle for observing the progress of a retry loop from elsewhere.
+///
+/// The values are updated at attempt boundaries (when each attempt starts).
+/// Cloning a handle is cheap, and clones observe the same loop.
+#[derive(Clone, Debug)]
+pub struct RetryHandle {
+    state: Arc<HandleState>,
+}
+
+#[derive(Debug)]
+struct HandleState {
+    attempts: AtomicU32,
+    max_attempts: u32,
+    started: OnceLock<Instant>,
+    max_elapsed: Option<Duration>,
+}
+
+impl RetryHandle {
+    fn new(max_retries: u32, max_elapsed: Option<Duration>) -> Self {
+        Self {
+            state: Arc::new(HandleState {
+                attempts: AtomicU32::new(0),
+                max_attempts: max_retries.saturating_add(1),
+                started: OnceLock::new(),
+                max_elapsed,
+            }),
+        }
+    }
+
+    /// Return the number of attempts that have been started so far.
+    pub fn attempts_so_far(&self) -> u32 {
+        self.state.attempts.load(Ordering::Acquire)
+    }
+
+    /// Return the number of attempts remaining in the retry budget.
+    ///
+    /// The loop may stop before using them, if it succeeds or if its policy
+    /// stops it.
+    pub fn remaining_budget(&self) -> u32 {
+        self.state
+            .max_attempts
+            .saturating_sub(self.attempts_so_far())
+    }
+
+    /// Return the time remaining before the error type's maximum elapsed time
+    /// is reached (or an empty value if it doesn't have one).
+    ///
+    /// This is measured from the start of the first attempt, so the full
+    /// maximum remains until the loop has started.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.state.max_elapsed.map(|max_elapsed| {
+            self.state.started.get().map_or(max_elapsed, |started| {
+                max_elapsed.saturating_sub(started.elapsed())
+            })
+        })
+    }
+}
+
+/// Execute a future with retries, returning a handle that can be used to
+/// observe its progress.
+pub fn retry_future_with_handle<F, Fut, T, E>(
+    mut f: F,
+) -> (impl Future<Output = Result<T, E>>, RetryHandle)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let handle = RetryHandle::new(effective_max_retries::<E>(), E::max_elapsed());
+    let state = handle.state.clone();
+
+    let future = retry_with_defaults(move || {
+        state.started.get_or_init(Instant::now);
+        state.attempts.fetch_add(1, Ordering::AcqRel);
+        f()
+    });
+
+    (future, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::poll_fn;
+    use std::pin::pin;
+    use std::task::Poll;
+    use tryhard::RetryPolicy;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct Error;
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            4
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(1)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn handle() {
+        let (future, handle) = retry_future_with_handle(|| async { Err::<(), _>(Error) });
+        let mut future = pin!(future);
+
+        assert_eq!(handle.attempts_so_far(), 0);
+        assert_eq!(handle.remaining_budget(), 5);
+
+        // The first attempt fails immediately, and the loop starts waiting.
+        poll_fn(|cx| Poll::Ready(future.as_mut().poll(cx).is_pending())).await;
+        assert_eq!(handle.attempts_so_far(), 1);
+        assert_eq!(handle.remaining_budget(), 4);
+
+        assert_eq!(future.await, Err(Error));
+        assert_eq!(handle.clone().attempts_so_far(), 5);
+        assert_eq!(handle.remaining_budget(), 0);
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct LimitedError;
+
+    impl Retryable for LimitedError {
+        fn max_retries() -> u32 {
+            10
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn max_elapsed() -> Option<Duration> {
+            Some(Duration::from_secs(1))
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn remaining_time() {
+        let (future, handle) = retry_future_with_handle(|| async { Err::<(), _>(LimitedError) });
+        let mut future = pin!(future);
+
+        assert_eq!(handle.remaining_time(), Some(Duration::from_secs(1)));
+
+        // The first delay is 100 milliseconds.
+        poll_fn(|cx| Poll::Ready(future.as_mut().poll(cx).is_pending())).await;
+        tokio::time::advance(Duration::from_millis(50)).await;
+        assert_eq!(handle.remaining_time(), Some(Duration::from_millis(950)));
+
+        assert_eq!(future.await, Err(LimitedError));
+        assert_eq!(handle.remaining_time(), Some(Duration::from_millis(300)));
+
+        let (_, handle) = retry_future_with_handle(|| async { Err::<(), _>(Error) });
+        assert_eq!(handle.remaining_time(), None);
+    }
+}
@@ -0,0 +1,160 @@
+//! Adapting retry delays to an external health signal.
+use crate::policy::Decision;
+use crate::{config_with_backoff, retry_with_config, scale_delay, ErrorBackoff, Retryable};
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use tryhard::{backoff_strategies::BackoffStrategy, RetryPolicy};
+
+/// The health of a downstream dependency.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum HealthState {
+    /// Delays are used as computed.
+    #[default]
+    Healthy,
+    /// Delays are doubled.
+    Degraded,
+    /// Delays are multiplied by four.
+    Unhealthy,
+}
+
+impl HealthState {
+    /// Return the factor that delays are multiplied by in this state.
+    pub fn delay_factor(self) -> f64 {
+        match self {
+            Self::Healthy => 1.0,
+            Self::Degraded => 2.0,
+            Self::Unhealthy => 4.0,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Healthy,
+            1 => Self::Degraded,
+            _ => Self::Unhealthy,
+        }
+    }
+}
+
+/// A shared health signal, typically updated by a background task that
+/// tracks a downstream dependency.
+///
+/// Clones share the same state, which starts healthy by default.
+#[derive(Clone, Debug, Default)]
+pub struct Health {
+    state: Arc<AtomicU8>,
+}
+
+impl Health {
+    /// Create a health signal in the given state.
+    pub fn new(state: HealthState) -> Self {
+        Self {
+            state: Arc::new(AtomicU8::new(state as u8)),
+        }
+    }
+
+    /// Return the current state.
+    pub fn get(&self) -> HealthState {
+        HealthState::from_u8(self.state.load(Ordering::Relaxed))
+    }
+
+    /// Update the state.
+    pub fn set(&self, state: HealthState) {
+        self.state.store(state as u8, Ordering::Relaxed);
+    }
+}
+
+/// Execute a future with retries, scaling each delay by the delay factor of
+/// the current health state.
+///
+/// The state is read when each delay is decided, so a change takes effect at
+/// the next retry decision of loops that are already running. Custom delays
+/// are scaled as well as default ones, and the scaling is applied after the
+/// maximum elapsed time has been checked against the computed delay.
+pub fn retry_future_health<F, Fut, T, E>(
+    f: F,
+    health: &Health,
+) -> impl Future<Output = Result<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let health = health.clone();
+
+    retry_with_config(f, move || {
+        config_with_backoff::<E, _>(HealthBackoff {
+            backoff: E::new_backoff(),
+            health,
+        })
+    })
+}
+
+struct HealthBackoff<E> {
+    backoff: ErrorBackoff<E>,
+    health: Health,
+}
+
+impl<'a, E: Retryable> BackoffStrategy<'a, E> for HealthBackoff<E> {
+    type Output = RetryPolicy;
+
+    fn delay(&mut self, attempt: u32, error: &'a E) -> RetryPolicy {
+        match Decision::from(self.backoff.delay(attempt, error)) {
+            Decision::Retry(delay) => {
+                Decision::Retry(scale_delay(delay, self.health.get().delay_factor()))
+            }
+            Decision::Stop => Decision::Stop,
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestError;
+    use std::sync::atomic::AtomicU32;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    type Error = TestError<100>;
+
+    #[test]
+    fn state() {
+        let health = Health::default();
+        assert_eq!(health.get(), HealthState::Healthy);
+
+        health.clone().set(HealthState::Degraded);
+        assert_eq!(health.get(), HealthState::Degraded);
+        assert_eq!(
+            Health::new(HealthState::Unhealthy).get(),
+            HealthState::Unhealthy
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn health_changes_mid_loop() {
+        let health = Health::default();
+        let attempts = AtomicU32::new(0);
+        let started = Instant::now();
+        let future = || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) == 2 {
+                health.set(HealthState::Unhealthy);
+            }
+
+            Err::<(), _>(Error::Transient)
+        };
+
+        assert_eq!(
+            retry_future_health(future, &health).await,
+            Err(Error::Transient)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+        assert_eq!(
+            started.elapsed(),
+            Duration::from_millis(100 + 200 + 400 * 4)
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! Recording the decisions made by a retry loop.
+use crate::policy::Decision;
+use crate::{config_with_backoff, retry_with_config, ErrorBackoff, Retryable};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use tryhard::{backoff_strategies::BackoffStrategy, RetryPolicy};
+
+/// Where the policy for a retry decision came from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PolicySource {
+    /// The error's `custom_retry_policy` returned a policy.
+    Custom,
+    /// The error had no custom policy, so the default exponential backoff
+    /// was used.
+    Default,
+}
+
+/// A record of the decision made after a failed attempt.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AttemptRecord {
+    /// The retry attempt that the decision was made for (starting at 1).
+    pub attempt: u32,
+    /// The delay before the next attempt (or an empty value if the loop
+    /// stopped).
+    pub delay: Option<Duration>,
+    /// The time that actually passed between the decision and the start of
+    /// the next attempt, which may be longer than the requested delay if the
+    /// runtime is overloaded (or an empty value if the loop stopped).
+    pub effective_delay: Option<Duration>,
+    /// Where the policy came from.
+    pub source: PolicySource,
+}
+
+/// Execute a future with retries, returning the result along with a record
+/// of each retry decision.
+///
+/// There is a record for each failed attempt that the policy was consulted
+/// for, which is every failed attempt except one that exhausts the retries.
+/// Only the most recent records are kept (see `max_history`), but since each
+/// record has its attempt number, the numbers stay accurate when the oldest
+/// ones have been dropped.
+pub fn retry_future_with_history<F, Fut, T, E>(
+    mut f: F,
+) -> impl Future<Output = (Result<T, E>, Vec<AttemptRecord>)>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let history = Arc::new(Mutex::new(History::new(E::max_history())));
+    let backoff_history = history.clone();
+
+    async move {
+        let result = retry_with_config(
+            || {
+                history.lock().unwrap().attempt_started();
+                f()
+            },
+            move || {
+                config_with_backoff::<E, _>(HistoryBackoff {
+                    backoff: E::new_backoff(),
+                    history: backoff_history,
+                })
+            },
+        )
+        .await;
+        let records = std::mem::take(&mut history.lock().unwrap().records);
+
+        (result, records.into())
+    }
+}
+
+struct History {
+    records: VecDeque<AttemptRecord>,
+    max_records: usize,
+    /// When the delay for the last record was decided.
+    decided: Option<Instant>,
+}
+
+impl History {
+    fn new(max_records: usize) -> Self {
+        Self {
+            records: VecDeque::new(),
+            max_records,
+            decided: None,
+        }
+    }
+
+    /// Add a record, dropping the oldest one if the limit has been reached.
+    fn push(&mut self, record: AttemptRecord) {
+        if self.records.len() == self.max_records {
+            self.records.pop_front();
+        }
+
+        if self.max_records > 0 {
+            self.records.push_back(record);
+        }
+    }
+
+    /// Record the effective delay for the last record.
+    fn attempt_started(&mut self) {
+        if let (Some(decided), Some(record)) = (self.decided.take(), self.records.back_mut()) {
+            record.effective_delay = Some(decided.elapsed());
+        }
+    }
+}
+
+struct HistoryBackoff<E> {
+    backoff: ErrorBackoff<E>,
+    history: Arc<Mutex<History>>,
+}
+
+impl<'a, E: Retryable> BackoffStrategy<'a, E> for HistoryBackoff<E> {
+    type Output = RetryPolicy;
+
+    fn delay(&mut self, attempt: u32, error: &'a E) -> RetryPolicy {
+        let policy = self.backoff.delay(attempt, error);
+
+        if let Some(source) = self.backoff.last_source() {
+            let delay = Decision::from(policy.clone()).delay();
+            let mut history = self.history.lock().unwrap();
+
+            history.push(AttemptRecord {
+                attempt,
+                delay,
+                effective_delay: None,
+                source,
+            });
+            history.decided = delay.map(|_| Instant::now());
+        }
+
+        policy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub enum Error {
+        Transient,
+        RateLimited,
+        Fatal,
+    }
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            5
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(10)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            match self {
+                Self::Transient => None,
+                Self::RateLimited => Some(RetryPolicy::Delay(Duration::from_secs(1))),
+                Self::Fatal => Some(RetryPolicy::Break),
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn history() {
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 2 => Err::<(), _>(Error::Transient),
+                1 => Err(Error::RateLimited),
+                _ => Err(Error::Fatal),
+            }
+        };
+
+        let (result, records) = retry_future_with_history(future).await;
+
+        assert_eq!(result, Err(Error::Fatal));
+        assert_eq!(
+            records,
+            vec![
+                AttemptRecord {
+                    attempt: 1,
+                    delay: Some(Duration::from_millis(10)),
+                    effective_delay: Some(Duration::from_millis(10)),
+                    source: PolicySource::Default,
+                },
+                AttemptRecord {
+                    attempt: 2,
+                    delay: Some(Duration::from_secs(1)),
+                    effective_delay: Some(Duration::from_secs(1)),
+                    source: PolicySource::Custom,
+                },
+                AttemptRecord {
+                    attempt: 3,
+                    delay: Some(Duration::from_millis(20)),
+                    effective_delay: Some(Duration::from_millis(20)),
+                    source: PolicySource::Default,
+                },
+                AttemptRecord {
+                    attempt: 4,
+                    delay: None,
+                    effective_delay: None,
+                    source: PolicySource::Custom,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn effective_delay() {
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                Err(Error::Transient)
+            } else {
+                Ok(())
+            }
+        };
+
+        let (result, records) = retry_future_with_history(future).await;
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(records.len(), 1);
+        assert!(records[0].effective_delay.unwrap() >= records[0].delay.unwrap());
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct LongError;
+
+    impl Retryable for LongError {
+        fn max_retries() -> u32 {
+            10
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(1)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn max_history() -> usize {
+            3
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            Some(RetryPolicy::Delay(Duration::from_millis(1)))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn max_history() {
+        let (result, records) =
+            retry_future_with_history(|| async { Err::<(), _>(LongError) }).await;
+
+        assert_eq!(result, Err(LongError));
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| record.attempt)
+                .collect::<Vec<_>>(),
+            vec![8, 9, 10]
+        );
+        assert!(records
+            .iter()
+            .all(|record| record.effective_delay.is_some()));
+    }
+}
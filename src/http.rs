@@ -0,0 +1,142 @@
+//! Retry policies for HTTP status codes and rate limits.
+use std::time::{Duration, SystemTime};
+use tryhard::RetryPolicy;
+
+/// Return a retry policy for an HTTP response status code.
+///
+/// This is intended to be called from a `custom_retry_policy` implementation,
+/// and doesn't depend on any particular HTTP client. The rules are:
+///
+/// * `429` and `503`: wait for `retry_after` if the server provided it,
+///   otherwise use the default backoff.
+/// * Any other `5xx`: use the default backoff.
+/// * Any other `4xx`: don't retry.
+///
+/// Other status codes have no policy (an empty value, as for `5xx`).
+pub fn policy_for_status(status: u16, retry_after: Option<Duration>) -> Option<RetryPolicy> {
+    match status {
+        429 | 503 => retry_after.map(RetryPolicy::Delay),
+        400..=499 => Some(RetryPolicy::Break),
+        _ => None,
+    }
+}
+
+/// Return a policy that waits until the given time.
+///
+/// This is useful for rate limits that report the time at which they reset
+/// (for example in a `X-RateLimit-Reset` header, which is commonly given in
+/// seconds since the Unix epoch). If the time has already passed, the delay
+/// is zero.
+///
+/// ```
+/// use retryable_error::delay_until;
+/// use std::time::{Duration, SystemTime, UNIX_EPOCH};
+/// use tryhard::RetryPolicy;
+///
+/// let reset = SystemTime::now() + Duration::from_secs(60);
+/// let reset_header = reset.duration_since(UNIX_EPOCH).unwrap().as_secs();
+///
+/// let policy = delay_until(UNIX_EPOCH + Duration::from_secs(reset_header));
+///
+/// assert!(matches!(policy, RetryPolicy::Delay(delay) if delay <= Duration::from_secs(60)));
+/// ```
+pub fn delay_until(time: SystemTime) -> RetryPolicy {
+    RetryPolicy::Delay(
+        time.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Retryable;
+    use tryhard::backoff_strategies::BackoffStrategy;
+
+    #[derive(Debug)]
+    pub enum Error {
+        RateLimited { reset: SystemTime },
+        Unavailable,
+    }
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            5
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            match self {
+                Self::RateLimited { reset } => Some(delay_until(*reset)),
+                Self::Unavailable => policy_for_status(503, None),
+            }
+        }
+
+        fn seed_delay(&self) -> Option<Duration> {
+            match self {
+                Self::RateLimited { .. } => Some(Duration::from_secs(1)),
+                Self::Unavailable => None,
+            }
+        }
+    }
+
+    #[test]
+    fn rate_limit_then_unavailable() {
+        let mut backoff = Error::new_backoff();
+
+        assert_eq!(
+            backoff.delay(1, &Error::Unavailable),
+            RetryPolicy::Delay(Duration::from_millis(100))
+        );
+
+        let reset = SystemTime::now() + Duration::from_secs(30);
+        match backoff.delay(2, &Error::RateLimited { reset }) {
+            RetryPolicy::Delay(delay) => {
+                assert!(delay > Duration::from_secs(29) && delay <= Duration::from_secs(30))
+            }
+            RetryPolicy::Break => panic!("Expected a delay"),
+        }
+
+        assert_eq!(
+            backoff.delay(3, &Error::Unavailable),
+            RetryPolicy::Delay(Duration::from_secs(1))
+        );
+        assert_eq!(
+            backoff.delay(4, &Error::Unavailable),
+            RetryPolicy::Delay(Duration::from_secs(2))
+        );
+    }
+
+    #[test]
+    fn delay_until_past() {
+        assert_eq!(
+            delay_until(SystemTime::now() - Duration::from_secs(1)),
+            RetryPolicy::Delay(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn policy_for_status_rules() {
+        let retry_after = Some(Duration::from_secs(3));
+
+        assert_eq!(
+            policy_for_status(429, retry_after),
+            Some(RetryPolicy::Delay(Duration::from_secs(3)))
+        );
+        assert_eq!(
+            policy_for_status(503, retry_after),
+            Some(RetryPolicy::Delay(Duration::from_secs(3)))
+        );
+        assert_eq!(policy_for_status(429, None), None);
+        assert_eq!(policy_for_status(500, retry_after), None);
+        assert_eq!(policy_for_status(404, None), Some(RetryPolicy::Break));
+        assert_eq!(policy_for_status(200, None), None);
+    }
+}
@@ -0,0 +1,278 @@
+//! Randomized jitter for default delays.
+use crate::{config_with_backoff, retry_with_config, scale_delay, Retryable};
+use rand::{Rng, RngCore};
+use std::fmt::Debug;
+use std::future::Future;
+use std::time::Duration;
+
+/// A strategy for randomizing default delays, so that many clients that fail
+/// at the same time don't all retry at the same time.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Jitter {
+    /// Use a uniform sample between zero and the delay.
+    Full,
+    /// Use half of the delay plus a uniform sample between zero and the other
+    /// half.
+    Equal,
+    /// Add a uniform sample between minus and plus the given window to the
+    /// delay (clamped to zero).
+    ///
+    /// Unlike the other strategies, the magnitude of the jitter doesn't grow
+    /// with the delay, which is useful for steady polling.
+    Absolute(#[cfg_attr(feature = "serde", serde(with = "crate::spec::millis"))] Duration),
+}
+
+impl Jitter {
+    /// Apply this jitter strategy to the given delay.
+    pub fn apply<R: Rng + ?Sized>(self, delay: Duration, rng: &mut R) -> Duration {
+        match self {
+            Self::Full => scale_delay(delay, rng.gen_range(0.0..=1.0)),
+            Self::Equal => {
+                (delay / 2).saturating_add(scale_delay(delay / 2, rng.gen_range(0.0..=1.0)))
+            }
+            Self::Absolute(window) => delay
+                .saturating_add(scale_delay(window, rng.gen_range(0.0..=2.0)))
+                .saturating_sub(window),
+        }
+    }
+}
+
+/// A random number generator for jitter that can be cloned along with the
+/// backoff strategy.
+pub(crate) trait JitterRng: RngCore + Send {
+    fn boxed_clone(&self) -> Box<dyn JitterRng>;
+}
+
+impl<R: RngCore + Clone + Send + 'static> JitterRng for R {
+    fn boxed_clone(&self) -> Box<dyn JitterRng> {
+        Box::new(self.clone())
+    }
+}
+
+/// Create a generator for jitter from a seed.
+pub(crate) fn seeded_rng(seed: u64) -> Box<dyn JitterRng> {
+    Box::new(<rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(
+        seed,
+    ))
+}
+
+/// Execute a future with retries, using the given random number generator
+/// for jitter.
+///
+/// This replaces the error type's `jitter_seed` (or the thread-local
+/// generator) for this loop only, so that a test can supply a scripted
+/// generator and check the exact jittered delays. It has no effect unless
+/// the error type has a jitter strategy.
+pub fn retry_future_with_rng<F, Fut, T, E, R>(f: F, rng: R) -> impl Future<Output = Result<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+    R: RngCore + Clone + Send + 'static,
+{
+    retry_with_config(f, move || {
+        config_with_backoff::<E, _>(E::new_backoff().with_rng(rng))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Retryable;
+    use tryhard::{backoff_strategies::BackoffStrategy, RetryPolicy};
+
+    #[derive(Debug)]
+    pub struct Error;
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            5
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn jitter() -> Option<Jitter> {
+            Some(Jitter::Absolute(Duration::from_millis(50)))
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct SeededError;
+
+    impl Retryable for SeededError {
+        fn max_retries() -> u32 {
+            5
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn jitter() -> Option<Jitter> {
+            Some(Jitter::Full)
+        }
+
+        fn jitter_seed() -> Option<u64> {
+            Some(1234)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    fn samples(jitter: Jitter, delay: Duration) -> Vec<Duration> {
+        let mut rng = rand::thread_rng();
+
+        (0..1000).map(|_| jitter.apply(delay, &mut rng)).collect()
+    }
+
+    #[test]
+    fn full_and_equal() {
+        let delay = Duration::from_millis(400);
+
+        assert!(samples(Jitter::Full, delay)
+            .into_iter()
+            .all(|sample| sample <= delay));
+        assert!(samples(Jitter::Equal, delay)
+            .into_iter()
+            .all(|sample| sample >= delay / 2 && sample <= delay));
+    }
+
+    #[test]
+    fn absolute() {
+        let window = Duration::from_millis(50);
+        let delay = Duration::from_millis(400);
+
+        assert!(samples(Jitter::Absolute(window), delay)
+            .into_iter()
+            .all(|sample| sample >= delay - window && sample <= delay + window));
+    }
+
+    #[test]
+    fn large_values() {
+        // These would overflow if they were scaled without saturating (the
+        // first is the largest window that can be deserialized).
+        let huge = Duration::from_millis(u64::MAX);
+
+        samples(Jitter::Absolute(huge), Duration::from_millis(400));
+        samples(Jitter::Absolute(Duration::MAX), Duration::from_millis(400));
+        samples(Jitter::Full, Duration::MAX);
+        samples(Jitter::Equal, Duration::MAX);
+    }
+
+    #[test]
+    fn absolute_non_negative() {
+        let window = Duration::from_millis(50);
+        let delay = Duration::from_millis(10);
+        let samples = samples(Jitter::Absolute(window), delay);
+
+        // Durations can't be negative, so this checks that the lower end of
+        // the window is clamped rather than saturating the whole sum.
+        assert!(samples.iter().all(|sample| *sample <= delay + window));
+        assert!(samples.iter().any(|sample| sample.is_zero()));
+    }
+
+    #[test]
+    fn backoff() {
+        let window = Duration::from_millis(50);
+        let mut backoff = Error::new_backoff();
+
+        for attempt in 1..=5 {
+            let base = Duration::from_millis(100 << (attempt - 1));
+
+            match backoff.delay(attempt, &Error) {
+                RetryPolicy::Delay(delay) => {
+                    assert!(delay >= base - window && delay <= base + window)
+                }
+                other => panic!("Unexpected policy: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn seeded() {
+        let delays = || {
+            let mut backoff = SeededError::new_backoff();
+
+            (1..=5)
+                .map(|attempt| backoff.delay(attempt, &SeededError))
+                .collect::<Vec<_>>()
+        };
+
+        let first = delays();
+
+        assert_eq!(first, delays());
+        assert!(first
+            .iter()
+            .any(|policy| *policy != first[0] && matches!(policy, RetryPolicy::Delay(_))));
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct EqualError;
+
+    impl Retryable for EqualError {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn jitter() -> Option<Jitter> {
+            Some(Jitter::Equal)
+        }
+
+        fn jitter_seed() -> Option<u64> {
+            Some(1234)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn with_rng() {
+        let started = tokio::time::Instant::now();
+        let attempted = std::sync::Mutex::new(vec![]);
+        let future = || async {
+            attempted
+                .lock()
+                .unwrap()
+                .push(started.elapsed().as_millis());
+            Err::<(), _>(EqualError)
+        };
+
+        // A generator that always returns zero gives the minimum jitter,
+        // which is half of each delay.
+        let result = retry_future_with_rng(future, rand::rngs::mock::StepRng::new(0, 0)).await;
+
+        assert_eq!(result, Err(EqualError));
+        assert_eq!(*attempted.lock().unwrap(), vec![0, 50, 150, 350]);
+    }
+}
@@ -1,23 +1,644 @@
 //! Tools for attaching retry logic to error types.
 use log::{log, Level};
+use policy::Decision;
 use std::fmt::Debug;
 use std::future::Future;
 use std::marker::PhantomData;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::task::{Context, Poll};
 use std::time::Duration;
-use tryhard::{
-    backoff_strategies::BackoffStrategy, OnRetry, RetryFuture, RetryFutureConfig, RetryPolicy,
+use tokio::time::Instant;
+use tryhard::{backoff_strategies::BackoffStrategy, OnRetry, RetryFutureConfig, RetryPolicy};
+
+mod abortable;
+mod adaptive;
+mod async_policy;
+mod boxed;
+mod breaker;
+mod classifier;
+mod combined;
+mod ctx;
+mod detailed;
+mod driver;
+mod dry_run;
+#[cfg(feature = "either")]
+mod either;
+mod error;
+mod escalate;
+mod ext;
+mod finally;
+mod forward;
+mod handle;
+mod health;
+mod history;
+mod http;
+#[cfg(feature = "jitter")]
+mod jitter;
+mod limited;
+mod marker;
+mod operation;
+mod outcome;
+mod partial;
+mod pause;
+mod policy;
+mod session;
+mod sleeper;
+#[cfg(feature = "serde")]
+mod spec;
+#[cfg(feature = "tracing")]
+mod summary;
+#[cfg(test)]
+mod test_support;
+mod timeout;
+mod until;
+mod window;
+
+pub use abortable::{retry_future_abortable, retry_future_abortable_result};
+pub use adaptive::{retry_future_adaptive, AdaptiveGate};
+pub use async_policy::{retry_future_async_policy, AsyncRetryable};
+pub use boxed::{retry_boxed, retry_boxed_with_config};
+pub use breaker::{retry_future_breaker, BreakerError, BreakerState, CircuitBreaker};
+pub use classifier::{retry_future_classified, Classifier};
+pub use combined::{retry_future_combined, CombineRule, PolicyCombinator};
+pub use ctx::{retry_future_with_ctx, RetryContext};
+pub use detailed::{
+    retry_future_detailed, retry_future_named, retry_future_with_stats, ReportStrategy,
+    RetryFailure, RetryStats, StopReason,
+};
+pub use driver::{RetryDriver, RetryStep};
+pub use dry_run::retry_future_dry_run;
+pub use error::RetryableError;
+pub use escalate::retry_future_escalate;
+pub use ext::RetryableFutureExt;
+pub use finally::retry_future_finally;
+pub use handle::{retry_future_with_handle, RetryHandle};
+pub use health::{retry_future_health, Health, HealthState};
+pub use history::{retry_future_with_history, AttemptRecord, PolicySource};
+pub use http::{delay_until, policy_for_status};
+#[cfg(feature = "jitter")]
+pub use jitter::{retry_future_with_rng, Jitter};
+pub use limited::{retry_future_limited, LimitedError};
+pub use marker::{RetryAlways, RetryNever};
+pub use operation::{retry_operation, Operation};
+pub use outcome::{retry_future_result, RetryError};
+pub use partial::retry_future_partial;
+pub use pause::{retry_future_pausable, PauseSwitch};
+pub use session::RetrySession;
+pub use sleeper::{retry_future_with_sleeper, Sleeper, TokioSleeper};
+#[cfg(feature = "serde")]
+pub use spec::{retry_future_with_spec, RetryConfigSpec};
+pub use timeout::{
+    retry_future_growing, retry_future_with_attempt_timeout, AttemptTimeoutError, GrowthSpec,
 };
+pub use until::{retry_until_true, UntilTrueError};
+pub use window::RetryWindow;
+
+static RETRIES_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Enable or disable retries for every retry loop in the process.
+///
+/// This is intended as an operational kill switch: while retries are
+/// disabled, every operation is attempted exactly once, regardless of its
+/// policy. The change takes effect immediately (at the next retry decision)
+/// for loops that are already running, and it is safe to call from any
+/// thread.
+///
+/// Error types that provide an `on_not_attempted` error aren't attempted at
+/// all by loops that start while retries are disabled (except for
+/// `retry_future`, which always makes the first attempt).
+pub fn set_retries_enabled(enabled: bool) {
+    RETRIES_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Indicate whether retries are currently enabled (see
+/// `set_retries_enabled`).
+pub fn retries_enabled() -> bool {
+    RETRIES_ENABLED.load(Ordering::Relaxed)
+}
+
+static OPERATIONS: AtomicU64 = AtomicU64::new(0);
+static ATTEMPTS: AtomicU64 = AtomicU64::new(0);
+
+/// Return the number of attempts per operation for every retry loop in the
+/// process since the counters were last reset.
+///
+/// This is a cheap indicator of retry storms: a value near `1.0` means that
+/// operations are generally succeeding (or giving up) at the first attempt,
+/// while a rising value means that retries are amplifying the load on
+/// whatever is failing. If no operations have been attempted, this is `1.0`.
+///
+/// The counters are cumulative, so for a value over a window (such as the
+/// last minute), read and then reset them at the end of each window with
+/// `reset_retry_amplification`.
+pub fn retry_amplification() -> f64 {
+    let operations = OPERATIONS.load(Ordering::Relaxed);
+    let attempts = ATTEMPTS.load(Ordering::Relaxed);
+
+    if operations == 0 {
+        1.0
+    } else {
+        attempts as f64 / operations as f64
+    }
+}
+
+/// Reset the counters used by `retry_amplification`.
+///
+/// The two counters are reset separately, so attempts made by loops that are
+/// running at the time may be counted against either window.
+pub fn reset_retry_amplification() {
+    OPERATIONS.store(0, Ordering::Relaxed);
+    ATTEMPTS.store(0, Ordering::Relaxed);
+}
+
+/// Count an operation and its first attempt for `retry_amplification`.
+pub(crate) fn record_operation() {
+    OPERATIONS.fetch_add(1, Ordering::Relaxed);
+    ATTEMPTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Count a retried attempt for `retry_amplification`.
+///
+/// Retries are counted by the retry handler when it's told that the loop
+/// will retry, so that loops built directly on `tryhard` count them too.
+fn record_retry() {
+    ATTEMPTS.fetch_add(1, Ordering::Relaxed);
+}
 
 /// Execute a future with retries where the error type is `Retryable`.
-pub fn retry_future<F, Fut, T, E>(f: F) -> RetryFuture<F, Fut, ErrorBackoff<E>, LogOnRetry>
+///
+/// A delay only starts once an attempt has failed, and the next attempt isn't
+/// started until it has finished, so a successful attempt is returned as soon
+/// as it completes, with no pending delay to wait out or cancel.
+///
+/// The loop doesn't spawn any tasks, so dropping the future (for example when
+/// another branch of a `select!` completes first) cancels it completely: the
+/// pending attempt or delay is dropped along with the operation and anything
+/// it has captured. The same is true of the other retry functions, except
+/// that `retry_future_finally` deliberately spawns its cleanup.
+///
+/// A delay that is zero after all of the error type's configuration has been
+/// applied (for example a zero initial delay, or a `Delay(Duration::ZERO)`
+/// custom policy) is raised to `MIN_RETRY_DELAY`, a single tick of the Tokio
+/// timer, so that the task yields to the executor before each retry and a
+/// loop of immediate retries can't starve other tasks.
+///
+/// The retry configuration is created when the future is first polled, so the
+/// elapsed time for `max_elapsed` and the logs is measured from the first
+/// attempt, not from this call. Unlike the other retry functions, this always
+/// makes the first attempt (the error type's `on_not_attempted` isn't
+/// consulted).
+///
+/// If the `tracing` feature is enabled, a `tracing` event carrying the error
+/// type name and the maximum number of retries is emitted when the loop starts,
+/// so that retries can be correlated with the operation even when it succeeds
+/// immediately. If several operations share an error type,
+/// `retry_future_named` also identifies the operation in the event and in
+/// every log message.
+///
+/// The operation can be any function that produces a future, including an
+/// `async fn` item:
+///
+/// ```
+/// use retryable_error::{retry_future, SimpleRetryable};
+///
+/// #[derive(Debug)]
+/// struct Error;
+///
+/// impl SimpleRetryable for Error {
+///     const MAX_RETRIES: u32 = 3;
+///     const INITIAL_DELAY_MS: u64 = 10;
+/// }
+///
+/// async fn fetch() -> Result<&'static str, Error> {
+///     Ok("foo")
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// assert_eq!(retry_future(fetch).await.unwrap(), "foo");
+/// # }
+/// ```
+pub async fn retry_future<F, Fut, T, E>(f: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    trace_start::<E>(effective_max_retries::<E>());
+    record_operation();
+
+    tryhard::retry_fn(f).with_config(E::retry_config()).await
+}
+
+/// The shortest delay before a retry in the loops that are run by `tryhard`
+/// (such as `retry_future`), which is one tick of the Tokio timer.
+///
+/// Sleeping for a zero delay may complete without yielding (for example when
+/// the clock is paused), so a zero delay is raised to this minimum to make sure
+/// that the task yields before the retry.
+pub const MIN_RETRY_DELAY: Duration = Duration::from_millis(1);
+
+/// Raise a zero delay to `MIN_RETRY_DELAY`.
+pub(crate) fn at_least_min_retry_delay(policy: RetryPolicy) -> RetryPolicy {
+    match Decision::from(policy) {
+        Decision::Retry(delay) if delay.is_zero() => RetryPolicy::Delay(MIN_RETRY_DELAY),
+        decision => decision.into(),
+    }
+}
+
+/// Execute a future with retries using the error type's configuration (as
+/// for `retry_future`, but honoring every option).
+pub(crate) fn retry_with_defaults<F, Fut, T, E>(f: F) -> impl Future<Output = Result<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    retry_with_config(f, E::retry_config)
+}
+
+/// Execute a future with retries, returning a boxed future.
+///
+/// This is useful in trait objects and other places where the future type
+/// needs to be named. The closure, the futures it returns, and the output
+/// must all be `Send`, so that the boxed future is `Send`.
+pub fn retry_future_boxed<'a, F, Fut, T, E>(
+    f: F,
+) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'a>>
+where
+    F: FnMut() -> Fut + Send + 'a,
+    Fut: Future<Output = Result<T, E>> + Send + 'a,
+    T: Send + 'a,
+    E: Retryable + Debug + Send + 'a,
+{
+    Box::pin(retry_with_defaults(f))
+}
+
+/// Execute a future with retries, returning the number of the successful
+/// attempt (counting from one) along with the value.
+pub async fn retry_future_attempt<F, Fut, T, E>(mut f: F) -> Result<(T, u32), E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let mut attempts = 0;
+    let value = retry_with_defaults(|| {
+        attempts += 1;
+        f()
+    })
+    .await?;
+
+    Ok((value, attempts))
+}
+
+/// Execute a future with retries using the given backoff instead of the error
+/// type's default.
+///
+/// The backoff's elapsed time is measured from the first attempt, so the
+/// backoff can be created ahead of time.
+pub fn retry_future_with_backoff<F, Fut, T, E>(
+    f: F,
+    backoff: ErrorBackoff<E>,
+) -> impl Future<Output = Result<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    retry_with_config(f, move || {
+        let mut backoff = backoff;
+        // The backoff may have been created well before the loop starts.
+        backoff.restart_elapsed();
+
+        config_with_backoff::<E, _>(backoff)
+    })
+}
+
+/// Execute a future with retries, passing every resolved policy through the
+/// given function, which returns the policy that is actually used.
+///
+/// The function is called with the attempt number, the error, and the policy
+/// that the error type's configuration produced (after the custom policy, the
+/// default delay and its caps, and the maximum elapsed time have all been
+/// applied). Its result is final: it can replace a delay, force a `Break`, or
+/// retry an error whose policy is `Break`. The only decisions that it can't
+/// override are the maximum number of retries and the kill switch (see
+/// `set_retries_enabled`), which stops the loop without calling it.
+pub fn retry_future_with_override<F, Fut, T, E, O>(
+    f: F,
+    override_fn: O,
+) -> impl Future<Output = Result<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+    O: FnMut(u32, &E, RetryPolicy) -> RetryPolicy,
+{
+    retry_with_config(f, move || {
+        config_with_backoff::<E, _>(OverrideBackoff {
+            override_fn,
+            backoff: E::new_backoff(),
+        })
+    })
+}
+
+/// Execute a future with retries using the given retry handler in place of
+/// logging.
+///
+/// The backoff and policies are the same as for `retry_future`. This is
+/// useful for plugging in metrics or other instrumentation (see
+/// `Retryable::retry_config_with_on_retry`).
+pub fn retry_future_with_on_retry<F, Fut, T, E, R>(
+    f: F,
+    on_retry: R,
+) -> impl Future<Output = Result<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+    R: OnRetry<E>,
+{
+    retry_with_config(f, move || {
+        E::retry_config_with_on_retry(CountRetries(on_retry))
+    })
+}
+
+/// Execute a future with retries, calling the given function with the delay
+/// just before each wait between attempts.
+///
+/// This is a lightweight hook for things like progress indicators. It's
+/// called synchronously from the retry loop, so it should return quickly.
+pub fn retry_future_with_on_sleep<F, Fut, T, E, S>(
+    f: F,
+    on_sleep: S,
+) -> impl Future<Output = Result<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+    S: Fn(Duration) + Send + 'static,
+{
+    retry_with_config(f, move || {
+        RetryFutureConfig::new(effective_max_retries::<E>())
+            .on_retry(LogOnRetry::new::<E>().with_on_sleep(on_sleep))
+            .custom_backoff(E::new_backoff())
+    })
+}
+
+/// Execute a future with retries, using the given function in place of
+/// `max_retries` for the error type.
+///
+/// The function is called once for each retry loop (when this function is
+/// called), so values from dynamic configuration (such as a feature flag
+/// system) are picked up by the next loop. The value is interpreted in the
+/// same way as `max_retries` (including `count_first_attempt`), and it's used
+/// in the log messages as well (`worst_case_total_delay_with_retries` gives
+/// the longest total delay for it).
+pub fn retry_future_dyn_retries<F, Fut, T, E, M>(
+    f: F,
+    max_retries: M,
+) -> impl Future<Output = Result<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+    M: Fn() -> u32,
+{
+    let configured = max_retries();
+    let max_retries = retries_after_first::<E>(configured);
+
+    unless_not_attempted(
+        configured,
+        retry_with_options(
+            f,
+            move || {
+                let mut backoff = E::new_backoff();
+                backoff.set_max_retries(max_retries);
+
+                RetryFutureConfig::new(max_retries)
+                    .on_retry(LogOnRetry::new::<E>().with_max_retries(max_retries))
+                    .custom_backoff(backoff)
+            },
+            max_retries,
+        ),
+    )
+}
+
+/// Return the longest total time that a retry loop for an error type could
+/// spend waiting between attempts.
+///
+/// This is the sum of the default delays for every retry (using the largest
+/// delay that the jitter strategy could produce), capped at the maximum delay
+/// and limited by the maximum elapsed time. Custom policies and anything else
+/// that depends on the error values (such as `max_delay_at` and
+/// `seed_delay`) aren't taken into account, so this is zero if the default is
+/// to break. Once a delay reaches the maximum delay, the later ones are assumed
+/// to stay there (as they do unless `next_delay` shrinks them), so that this
+/// is cheap to compute even for very large numbers of retries.
+pub fn worst_case_total_delay<E: Retryable + ?Sized>() -> Duration {
+    worst_case_delay_for::<E>(effective_max_retries::<E>())
+}
+
+/// Return the longest total time that a retry loop for an error type could
+/// spend waiting between attempts (as for `worst_case_total_delay`), given a
+/// value to use in place of `max_retries` (as for `retry_future_dyn_retries`).
+pub fn worst_case_total_delay_with_retries<E: Retryable + ?Sized>(max_retries: u32) -> Duration {
+    worst_case_delay_for::<E>(retries_after_first::<E>(max_retries))
+}
+
+/// Return the longest total delay for the given number of retries after the
+/// first attempt.
+fn worst_case_delay_for<E: Retryable + ?Sized>(max_retries: u32) -> Duration {
+    if E::default_is_break() {
+        return Duration::ZERO;
+    }
+
+    let at_least_min_delay =
+        |delay: Duration| E::min_delay().map_or(delay, |min_delay| delay.max(min_delay));
+    let mut delay = BackoffSettings::for_type::<E>().initial_delay;
+    let mut total = Duration::ZERO;
+
+    for attempt in 1..=max_retries {
+        let prev_delay = at_least_min_delay(delay);
+        delay = E::next_delay(attempt, prev_delay);
+
+        #[cfg(feature = "jitter")]
+        let prev_delay = match E::jitter() {
+            Some(Jitter::Absolute(window)) => prev_delay.saturating_add(window),
+            _ => prev_delay,
+        };
+
+        let prev_delay =
+            E::quantize_delay().map_or(prev_delay, |quantum| quantize(prev_delay, quantum));
+
+        let capped = E::max_delay().map_or(prev_delay, |max_delay| prev_delay.min(max_delay));
+        total = total.saturating_add(capped);
+
+        // Once the delay is capped, the remaining retries all wait for the
+        // maximum delay.
+        if E::max_delay() == Some(capped) {
+            let remaining = max_retries - attempt;
+            total = total.saturating_add(capped.saturating_mul(remaining));
+            break;
+        }
+
+        if total == Duration::MAX
+            || E::max_elapsed().is_some_and(|max_elapsed| total >= max_elapsed)
+        {
+            break;
+        }
+    }
+
+    E::max_elapsed().map_or(total, |max_elapsed| total.min(max_elapsed))
+}
+
+/// Execute a future with retries where the policy for each error is given by
+/// a function instead of the error type's `custom_retry_policy`.
+pub(crate) fn retry_with_policy<F, Fut, T, E, C>(
+    f: F,
+    policy: C,
+) -> impl Future<Output = Result<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+    C: FnMut(&E) -> Option<RetryPolicy>,
+{
+    retry_with_config(f, move || {
+        config_with_backoff::<E, _>(PolicyBackoff {
+            policy,
+            backoff: E::new_backoff(),
+        })
+    })
+}
+
+/// Execute a future with retries where the policy for each error is given by
+/// a function, and the static configuration (such as the number of retries
+/// and the delays) comes from another type.
+pub(crate) fn retry_with_policy_from<S, F, Fut, T, E, C>(
+    f: F,
+    policy: C,
+) -> impl Future<Output = Result<T, E>>
+where
+    S: Retryable + ?Sized,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+    C: FnMut(&E) -> Option<RetryPolicy>,
+{
+    let max_retries = effective_max_retries::<S>();
+
+    retry_with_options(
+        f,
+        move || {
+            config_with_backoff::<S, _>(PolicyBackoff {
+                policy,
+                backoff: ErrorBackoff::from_settings(BackoffSettings::for_type::<S>()),
+            })
+        },
+        max_retries,
+    )
+}
+
+/// Execute a future with retries, using the configuration that the given
+/// function builds.
+///
+/// The configuration is built when the loop is first polled, just before the
+/// first attempt, so that the elapsed time is measured from that attempt
+/// rather than from when the future was created.
+fn retry_with_config<F, Fut, T, E, B, R, C>(f: F, config: C) -> impl Future<Output = Result<T, E>>
 where
     F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+    for<'a> B: BackoffStrategy<'a, E, Output = RetryPolicy>,
+    R: OnRetry<E>,
+    C: FnOnce() -> RetryFutureConfig<B, R>,
+{
+    unless_not_attempted(
+        E::max_retries(),
+        retry_with_options(f, config, effective_max_retries::<E>()),
+    )
+}
+
+/// Run the retry loop, unless the error type's `on_not_attempted` error
+/// applies, given the configured maximum number of retries.
+async fn unless_not_attempted<Fut, T, E>(max_retries: u32, future: Fut) -> Result<T, E>
+where
     Fut: Future<Output = Result<T, E>>,
     E: Retryable,
 {
-    tryhard::retry_fn(f).with_config(E::retry_config())
+    match not_attempted::<E>(max_retries) {
+        Some(error) => Err(error),
+        None => future.await,
+    }
+}
+
+/// Return the error type's `on_not_attempted` error if it applies, given the
+/// configured maximum number of retries.
+pub(crate) fn not_attempted<E: Retryable>(max_retries: u32) -> Option<E> {
+    let no_attempts = !retries_enabled() || (E::count_first_attempt() && max_retries == 0);
+
+    E::on_not_attempted().filter(|_| no_attempts)
+}
+
+/// Execute a future with retries using the configuration that the given
+/// function builds (when the loop is first polled), without requiring the
+/// error type to be `Retryable`.
+///
+/// The number of retries is only used for the `tracing` event. The backoff
+/// strategy should raise zero delays to `MIN_RETRY_DELAY` (as the strategies
+/// in this crate do), and the retry handler should count the retries (as
+/// `LogOnRetry` and `CountRetries` do).
+pub(crate) async fn retry_with_options<F, Fut, T, E, B, R, C>(
+    f: F,
+    config: C,
+    max_retries: u32,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    for<'a> B: BackoffStrategy<'a, E, Output = RetryPolicy>,
+    R: OnRetry<E>,
+    C: FnOnce() -> RetryFutureConfig<B, R>,
+{
+    trace_start::<E>(max_retries);
+    record_operation();
+
+    tryhard::retry_fn(f).with_config(config()).await
+}
+
+/// Indicate whether a retry loop for the error type should yield to the
+/// executor before a retry after the given delay.
+pub(crate) fn yield_before_retry<E: Retryable + ?Sized>(delay: Duration) -> bool {
+    E::yield_between_attempts() || delay.is_zero()
+}
+
+/// Emit a `tracing` event for the start of a named retry loop (if the
+/// `tracing` feature is enabled).
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn trace_start_named<E: ?Sized>(max_retries: u32, name: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        operation = name,
+        error_type = std::any::type_name::<E>(),
+        max_retries,
+        "Starting retryable operation"
+    );
+}
+
+/// Emit a `tracing` event for the start of a retry loop (if the `tracing`
+/// feature is enabled).
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn trace_start<E: ?Sized>(max_retries: u32) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        error_type = std::any::type_name::<E>(),
+        max_retries,
+        "Starting retryable operation"
+    );
 }
 
 pub struct LogFuture {
@@ -44,6 +665,143 @@ impl Future for LogFuture {
 
 pub struct LogOnRetry {
     level: Option<Level>,
+    escalating_levels: Option<&'static [Level]>,
+    started: Instant,
+    log_elapsed: bool,
+    log_once: Option<LogOnce>,
+    log_error_value: bool,
+    on_sleep: Option<Box<dyn Fn(Duration) + Send>>,
+    name: Option<&'static str>,
+    #[cfg(feature = "tracing")]
+    schedule: summary::DelaySchedule,
+}
+
+/// The description of a retry loop that is logged once.
+struct LogOnce {
+    error_type: &'static str,
+    max_retries: u32,
+}
+
+impl LogOnRetry {
+    pub(crate) fn new<E: Retryable + ?Sized>() -> Self {
+        Self {
+            log_elapsed: E::log_elapsed(),
+            log_once: E::log_once().then(|| LogOnce {
+                error_type: std::any::type_name::<E>(),
+                max_retries: effective_max_retries::<E>(),
+            }),
+            log_error_value: E::log_error_value(),
+            escalating_levels: E::escalating_log_levels(),
+            #[cfg(feature = "tracing")]
+            schedule: summary::DelaySchedule::for_type::<E>(),
+            ..Self::with_level(E::log_level())
+        }
+    }
+
+    pub(crate) fn with_level(level: Option<Level>) -> Self {
+        Self {
+            level,
+            escalating_levels: None,
+            started: Instant::now(),
+            log_elapsed: false,
+            log_once: None,
+            log_error_value: true,
+            on_sleep: None,
+            name: None,
+            #[cfg(feature = "tracing")]
+            schedule: summary::DelaySchedule::default(),
+        }
+    }
+
+    /// Describe the loop with the given maximum number of retries (after the
+    /// first attempt) in place of the error type's in log-once mode.
+    pub(crate) fn with_max_retries(mut self, max_retries: u32) -> Self {
+        if let Some(log_once) = &mut self.log_once {
+            log_once.max_retries = max_retries;
+        }
+
+        self
+    }
+
+    /// Prefix every message with the given operation name.
+    pub(crate) fn with_name(self, name: &'static str) -> Self {
+        Self {
+            name: Some(name),
+            #[cfg(feature = "tracing")]
+            schedule: self.schedule.named(name),
+            ..self
+        }
+    }
+
+    /// Call the given function with the delay whenever the retry loop is
+    /// about to wait before retrying.
+    pub(crate) fn with_on_sleep<S: Fn(Duration) + Send + 'static>(self, on_sleep: S) -> Self {
+        Self {
+            on_sleep: Some(Box::new(on_sleep)),
+            ..self
+        }
+    }
+
+    /// Return the level for messages about the given retry attempt.
+    fn level_for(&self, attempts: u32) -> Option<Level> {
+        match self.escalating_levels {
+            Some(levels) => {
+                let index = usize::try_from(attempts.saturating_sub(1)).unwrap_or(usize::MAX);
+
+                levels.get(index).or(levels.last()).copied()
+            }
+            None => self.level,
+        }
+    }
+
+    fn message<E: Debug>(&self, attempts: u32, delay: Duration, previous_error: &E) -> String {
+        let message = if let Some(log_once) = &self.log_once {
+            format!(
+                "Retrying {} with backoff (up to {} retries); waiting {:?}",
+                log_once.error_type, log_once.max_retries, delay
+            )
+        } else if self.log_elapsed {
+            format!(
+                "Retry {}; waiting {:?} ({:?} elapsed)",
+                attempts,
+                delay,
+                self.started.elapsed()
+            )
+        } else {
+            format!("Retry {}; waiting {:?}", attempts, delay)
+        };
+
+        self.with_error(message, previous_error)
+    }
+
+    /// Add the error to a message (unless the error type opts out), and the
+    /// operation name if there is one.
+    fn with_error<E: Debug>(&self, message: String, previous_error: &E) -> String {
+        let message = if self.log_error_value {
+            format!("{} after error: {:?}", message, previous_error)
+        } else {
+            message
+        };
+
+        match self.name {
+            Some(name) => format!("{}: {}", name, message),
+            None => message,
+        }
+    }
+
+    /// Log immediately instead of returning a future.
+    pub(crate) fn log_now<E: Debug>(
+        &mut self,
+        attempts: u32,
+        next_delay: Option<Duration>,
+        previous_error: &E,
+    ) {
+        let future = self.on_retry(attempts, next_delay, previous_error);
+
+        if let (Some(level), Some(message)) = (future.level, future.message) {
+            log!(level, "{}", message);
+        }
+    }
 }
 
 impl<E: Debug> OnRetry<E> for LogOnRetry {
@@ -55,22 +813,37 @@ impl<E: Debug> OnRetry<E> for LogOnRetry {
         next_delay: Option<Duration>,
         previous_error: &E,
     ) -> Self::Future {
-        match next_delay {
-            Some(delay) => {
-                let message = if self.level.is_none() {
-                    None
-                } else {
-                    Some(format!(
-                        "Retry {}; waiting {:?} after error: {:?}",
-                        attempts, delay, previous_error
-                    ))
-                };
+        if next_delay.is_some() {
+            record_retry();
+        }
+
+        if let (Some(delay), Some(on_sleep)) = (next_delay, &self.on_sleep) {
+            on_sleep(delay);
+        }
+
+        #[cfg(feature = "tracing")]
+        if let Some(delay) = next_delay {
+            self.schedule.record(delay);
+        }
+
+        // Only format the message if it will actually be logged.
+        match (next_delay, self.level_for(attempts), &self.log_once) {
+            (Some(delay), Some(level), log_once)
+                if (log_once.is_none() || attempts == 1) && log::log_enabled!(level) =>
+            {
                 LogFuture {
-                    level: self.level,
-                    message,
+                    level: Some(level),
+                    message: Some(self.message(attempts, delay, previous_error)),
                 }
             }
-            None => LogFuture {
+            (None, Some(level), Some(log_once)) if log::log_enabled!(level) => LogFuture {
+                level: Some(level),
+                message: Some(self.with_error(
+                    format!("Giving up on {}", log_once.error_type),
+                    previous_error,
+                )),
+            },
+            _ => LogFuture {
                 level: None,
                 message: None,
             },
@@ -78,78 +851,1947 @@ impl<E: Debug> OnRetry<E> for LogOnRetry {
     }
 }
 
+/// A retry handler that counts the retries for `retry_amplification` before
+/// passing them on to another handler.
+struct CountRetries<R>(R);
+
+impl<E, R: OnRetry<E>> OnRetry<E> for CountRetries<R> {
+    type Future = R::Future;
+
+    fn on_retry(
+        &mut self,
+        attempts: u32,
+        next_delay: Option<Duration>,
+        previous_error: &E,
+    ) -> Self::Future {
+        if next_delay.is_some() {
+            record_retry();
+        }
+
+        self.0.on_retry(attempts, next_delay, previous_error)
+    }
+}
+
 pub struct ErrorBackoff<E>
 where
     E: ?Sized,
 {
     delay: Duration,
+    initial_delay: Duration,
+    max_retries: u32,
+    started: Instant,
+    max_elapsed: Option<Duration>,
+    multiplier: f64,
+    next_delay: Option<fn(u32, Duration) -> Duration>,
+    min_delay: Option<Duration>,
+    default_is_break: bool,
+    quantum: Option<Duration>,
+    retry_window: Option<RetryWindow>,
+    fit_to_max_elapsed: bool,
+    first_retry_immediate: bool,
+    /// Whether the next default delay is replaced by an immediate retry.
+    immediate_pending: bool,
+    #[cfg(feature = "jitter")]
+    jitter: Option<Jitter>,
+    #[cfg(feature = "jitter")]
+    rng: Option<Box<dyn jitter::JitterRng>>,
+    last_source: Option<PolicySource>,
+    stop_reason: Option<StopReason>,
+    prev_key: Option<String>,
     _error: PhantomData<E>,
 }
 
+// This isn't derived, since that would require the error type to be `Clone`.
+impl<E: ?Sized> Clone for ErrorBackoff<E> {
+    fn clone(&self) -> Self {
+        Self {
+            delay: self.delay,
+            initial_delay: self.initial_delay,
+            max_retries: self.max_retries,
+            started: self.started,
+            max_elapsed: self.max_elapsed,
+            multiplier: self.multiplier,
+            next_delay: self.next_delay,
+            min_delay: self.min_delay,
+            default_is_break: self.default_is_break,
+            quantum: self.quantum,
+            retry_window: self.retry_window,
+            fit_to_max_elapsed: self.fit_to_max_elapsed,
+            first_retry_immediate: self.first_retry_immediate,
+            immediate_pending: self.immediate_pending,
+            #[cfg(feature = "jitter")]
+            jitter: self.jitter,
+            #[cfg(feature = "jitter")]
+            rng: self.rng.as_ref().map(|rng| rng.boxed_clone()),
+            last_source: self.last_source,
+            stop_reason: self.stop_reason,
+            prev_key: self.prev_key.clone(),
+            _error: PhantomData,
+        }
+    }
+}
+
 impl<'a, E: Retryable> BackoffStrategy<'a, E> for ErrorBackoff<E> {
     type Output = RetryPolicy;
 
-    fn delay(&mut self, _attempt: u32, error: &'a E) -> RetryPolicy {
-        error.custom_retry_policy().unwrap_or_else(|| {
-            let prev_delay = self.delay;
-            self.delay *= 2;
-            RetryPolicy::Delay(prev_delay)
-        })
+    fn delay(&mut self, attempt: u32, error: &'a E) -> RetryPolicy {
+        at_least_min_retry_delay(self.resolve_for(attempt, error, error.custom_retry_policy()))
     }
 }
 
-/// The `Retryable` trait allows an error type to define retry logic for
-/// specific errors.
-pub trait Retryable {
-    /// Return the maximum number of retries.
-    fn max_retries() -> u32;
+impl<E: Retryable> ErrorBackoff<E> {
+    /// Return the policy that `delay` would return for the given error and
+    /// attempt, without advancing the default delays.
+    ///
+    /// This is useful for displaying the next delay before committing to it.
+    /// If a jitter strategy is configured without a seed, the jittered delay
+    /// will generally differ from the one that `delay` returns.
+    pub fn peek_delay(&self, attempt: u32, error: &E) -> RetryPolicy {
+        self.clone().delay(attempt, error)
+    }
+}
 
-    /// Return the default initial delay.
-    fn default_initial_delay() -> Duration;
+impl<E: Retryable + ?Sized> ErrorBackoff<E> {
+    /// Resolve the policy for an error value, given its custom policy.
+    ///
+    /// This applies any per-error configuration (such as the maximum delay for
+    /// the attempt, the policy given the previous error or for the final
+    /// retry, and the seed delay) while resolving the policy.
+    pub(crate) fn resolve_for(
+        &mut self,
+        attempt: u32,
+        error: &E,
+        custom: Option<RetryPolicy>,
+    ) -> RetryPolicy {
+        self.decide_for(attempt, error, custom)
+            .map_or(RetryPolicy::Break, RetryPolicy::Delay)
+    }
 
-    /// Return the log level for this error type (an empty value indicates that
-    /// no logging will be done).
-    fn log_level() -> Option<Level>;
+    /// Return the delay before the next attempt for an error value, given its
+    /// custom policy, or the reason for stopping the loop (as for `decide`).
+    pub(crate) fn decide_for(
+        &mut self,
+        attempt: u32,
+        error: &E,
+        custom: Option<RetryPolicy>,
+    ) -> Result<Duration, StopReason> {
+        let custom = error
+            .policy_with_prev(self.prev_key.as_deref(), attempt)
+            .or(custom);
+        let custom = if attempt == self.max_retries {
+            error.final_attempt_policy().or(custom)
+        } else {
+            custom
+        };
+        self.prev_key = error.error_key();
 
-    /// Return a retry policy for the given error value.
-    ///
-    /// An empty value represents the default.
-    fn custom_retry_policy(&self) -> Option<RetryPolicy>;
+        let decision = self.decide(attempt, custom, error.max_delay_at(attempt));
 
-    /// Generate a new backoff strategy instance.
-    fn new_backoff() -> ErrorBackoff<Self> {
-        ErrorBackoff {
-            delay: Self::default_initial_delay(),
-            _error: PhantomData,
+        if let Some(seed_delay) = error.seed_delay() {
+            self.delay = seed_delay;
         }
+
+        decision
     }
 
-    /// Generate a new retry configuration instance.
-    fn retry_config() -> RetryFutureConfig<ErrorBackoff<Self>, LogOnRetry> {
-        RetryFutureConfig::new(Self::max_retries())
-            .on_retry(LogOnRetry {
-                level: Self::log_level(),
-            })
-            .custom_backoff(Self::new_backoff())
+    /// Create a backoff strategy for the error type with the given initial
+    /// delay in place of the default.
+    pub fn with_initial_delay(initial_delay: Duration) -> Self {
+        Self::from_settings(BackoffSettings {
+            initial_delay,
+            ..BackoffSettings::for_type::<E>()
+        })
+    }
+
+    /// Create a backoff strategy for the error type that retries immediately
+    /// after the first failure, and then uses the default delays.
+    ///
+    /// This suits operations whose failures are usually brief blips. The
+    /// default delays start from the initial delay after the immediate retry,
+    /// so with a 100 millisecond initial delay and the default multiplier,
+    /// the delays are 0 (or rather `MIN_RETRY_DELAY` when the loop is run by
+    /// `tryhard`), 100, 200, and 400 milliseconds, and so on. Only the
+    /// default delay for the first failure is replaced, so if that error has
+    /// a custom policy, it's used as usual (and there is no immediate retry).
+    pub fn quick_then_backoff() -> Self {
+        Self {
+            first_retry_immediate: true,
+            immediate_pending: true,
+            ..E::new_backoff()
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tokio::sync::Mutex;
+impl<E: ?Sized> ErrorBackoff<E> {
+    /// Create a backoff strategy from a snapshot of the configuration.
+    pub(crate) fn from_settings(settings: BackoffSettings) -> Self {
+        Self {
+            delay: settings.initial_delay,
+            initial_delay: settings.initial_delay,
+            max_retries: settings.max_retries,
+            started: Instant::now(),
+            max_elapsed: settings.max_elapsed,
+            multiplier: settings.multiplier,
+            next_delay: settings.next_delay,
+            min_delay: settings.min_delay,
+            default_is_break: settings.default_is_break,
+            quantum: settings.quantum,
+            retry_window: settings.retry_window,
+            fit_to_max_elapsed: settings.fit_to_max_elapsed,
+            first_retry_immediate: false,
+            immediate_pending: false,
+            #[cfg(feature = "jitter")]
+            jitter: settings.jitter,
+            #[cfg(feature = "jitter")]
+            rng: settings.jitter_seed.map(jitter::seeded_rng),
+            last_source: None,
+            stop_reason: None,
+            prev_key: None,
+            _error: PhantomData,
+        }
+    }
 
-    #[derive(thiserror::Error, Debug, Eq, PartialEq)]
-    pub enum Error {
-        #[error("Error A")]
-        A(String),
-        #[error("Error B")]
-        B(u64),
+    /// Restart the default delays from the initial delay and the elapsed time
+    /// from now (and forget the previous error, and allow another immediate
+    /// retry if the strategy was created by `quick_then_backoff`).
+    ///
+    /// This is useful for long-lived loops, where recovering from a transient
+    /// failure shouldn't leave later failures waiting for a long delay (or
+    /// past the maximum elapsed time before they've been retried at all).
+    pub fn reset(&mut self) {
+        self.delay = self.initial_delay;
+        self.started = Instant::now();
+        self.immediate_pending = self.first_retry_immediate;
+        self.prev_key = None;
     }
 
-    impl Retryable for Error {
+    /// Use the given random number generator for jitter in place of the seed
+    /// (or the thread-local generator).
+    #[cfg(feature = "jitter")]
+    pub(crate) fn with_rng<R: rand::RngCore + Clone + Send + 'static>(self, rng: R) -> Self {
+        Self {
+            rng: Some(Box::new(rng)),
+            ..self
+        }
+    }
+
+    /// Use the given maximum number of retries (after the first attempt) in
+    /// place of the error type's to identify the final retry.
+    pub(crate) fn set_max_retries(&mut self, max_retries: u32) {
+        self.max_retries = max_retries;
+    }
+
+    /// Measure the elapsed time that is checked against the maximum from now.
+    pub(crate) fn restart_elapsed(&mut self) {
+        self.started = Instant::now();
+    }
+
+    /// Exclude a period (such as a pause) from the elapsed time that is
+    /// checked against the maximum.
+    pub(crate) fn exclude_from_elapsed(&mut self, duration: Duration) {
+        self.started = self.started.checked_add(duration).unwrap_or(self.started);
+    }
+
+    /// Move the deadline for the maximum elapsed time to the given maximum after
+    /// the given instant, if this is earlier.
+    pub(crate) fn limit_elapsed(&mut self, started: Instant, max_elapsed: Duration) {
+        let current = self
+            .max_elapsed
+            .and_then(|current| self.started.checked_add(current));
+
+        let earlier = match (started.checked_add(max_elapsed), current) {
+            (Some(deadline), Some(current)) => deadline < current,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+
+        if earlier {
+            self.started = started;
+            self.max_elapsed = Some(max_elapsed);
+        }
+    }
+
+    /// Indicate whether the most recently resolved policy was custom or the
+    /// default (or return an empty value if no policy has been resolved yet).
+    pub fn last_source(&self) -> Option<PolicySource> {
+        self.last_source
+    }
+
+    /// Return the reason that this backoff strategy stopped the retry loop
+    /// (or an empty value if it hasn't).
+    ///
+    /// This is never `StopReason::Exhausted`, since the backoff strategy isn't
+    /// consulted when the retries are exhausted.
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason
+    }
+
+    /// Return the given custom delay, or the next default delay if there's no
+    /// custom policy, or the reason for stopping the loop, which is also
+    /// recorded for `stop_reason`.
+    ///
+    /// The default delay is jittered (if the `jitter` feature is enabled and
+    /// a strategy is configured) and then capped at the given maximum delay
+    /// (custom delays are never jittered or capped). If waiting for the
+    /// resulting delay would exceed the maximum elapsed time, if retries are
+    /// disabled, or if it's outside the retry window, the loop is stopped
+    /// instead. If the default is configured to break, an empty custom policy
+    /// also stops the loop.
+    pub(crate) fn decide(
+        &mut self,
+        attempt: u32,
+        custom: Option<RetryPolicy>,
+        max_delay: Option<Duration>,
+    ) -> Result<Duration, StopReason> {
+        let decision = self.decide_unrecorded(attempt, custom, max_delay);
+        self.stop_reason = decision.err();
+
+        decision
+    }
+
+    fn decide_unrecorded(
+        &mut self,
+        attempt: u32,
+        custom: Option<RetryPolicy>,
+        max_delay: Option<Duration>,
+    ) -> Result<Duration, StopReason> {
+        self.last_source = Some(if custom.is_some() {
+            PolicySource::Custom
+        } else {
+            PolicySource::Default
+        });
+
+        if !retries_enabled() {
+            return Err(StopReason::Disabled);
+        }
+
+        if let Some(window) = self.retry_window {
+            if !window.is_open() {
+                return Err(StopReason::OutsideWindow);
+            }
+        }
+
+        let fitted = custom.is_none() && self.fit_to_max_elapsed;
+        let immediate = std::mem::take(&mut self.immediate_pending);
+        let decision = match custom {
+            Some(policy) => Decision::from(policy),
+            None if self.default_is_break => Decision::Stop,
+            None if immediate => Decision::Retry(Duration::ZERO),
+            None => self.next_default_delay(attempt, max_delay),
+        };
+
+        match (decision, self.max_elapsed) {
+            (Decision::Retry(delay), Some(max_elapsed))
+                if self.started.elapsed().saturating_add(delay) > max_elapsed =>
+            {
+                let remaining = max_elapsed.saturating_sub(self.started.elapsed());
+
+                // A fitted schedule uses up whatever is left of the budget.
+                if fitted && !remaining.is_zero() {
+                    Ok(remaining)
+                } else {
+                    Err(StopReason::Deadline)
+                }
+            }
+            (Decision::Retry(delay), _) => Ok(delay),
+            (Decision::Stop, _) => Err(StopReason::Break),
+        }
+    }
+
+    /// Advance the default delays, returning the current one (jittered and
+    /// capped at the given maximum delay) for the given retry attempt.
+    fn next_default_delay(&mut self, attempt: u32, max_delay: Option<Duration>) -> Decision {
+        let prev_delay = self.at_least_min_delay(self.delay);
+        let next_delay = match self.next_delay {
+            Some(next_delay) => next_delay(attempt, prev_delay),
+            None => scale_delay(prev_delay, self.multiplier),
+        };
+
+        if let Some(min_delay) = self.min_delay {
+            if next_delay < min_delay && prev_delay > min_delay {
+                log::debug!(
+                    "Backoff multiplier {} reduced the delay below the minimum ({:?})",
+                    self.multiplier,
+                    min_delay
+                );
+            }
+        }
+
+        self.delay = self.at_least_min_delay(next_delay);
+
+        #[cfg(feature = "jitter")]
+        let prev_delay = match (self.jitter, self.rng.as_mut()) {
+            (Some(jitter), Some(rng)) => jitter.apply(prev_delay, &mut **rng),
+            (Some(jitter), None) => jitter.apply(prev_delay, &mut rand::thread_rng()),
+            (None, _) => prev_delay,
+        };
+
+        let prev_delay = self
+            .quantum
+            .map_or(prev_delay, |quantum| quantize(prev_delay, quantum));
+
+        Decision::Retry(max_delay.map_or(prev_delay, |max_delay| prev_delay.min(max_delay)))
+    }
+
+    fn at_least_min_delay(&self, delay: Duration) -> Duration {
+        self.min_delay
+            .map_or(delay, |min_delay| delay.max(min_delay))
+    }
+}
+
+/// A snapshot of the configuration of the default delays, which allows a
+/// backoff strategy to be built from values that aren't known statically.
+pub(crate) struct BackoffSettings {
+    pub(crate) initial_delay: Duration,
+    /// The loop's maximum number of retries after the first attempt, which
+    /// identifies the final retry.
+    pub(crate) max_retries: u32,
+    pub(crate) max_elapsed: Option<Duration>,
+    pub(crate) multiplier: f64,
+    /// The function computing each default delay from the previous one (or
+    /// an empty value to scale it by the multiplier).
+    pub(crate) next_delay: Option<fn(u32, Duration) -> Duration>,
+    pub(crate) min_delay: Option<Duration>,
+    pub(crate) default_is_break: bool,
+    pub(crate) quantum: Option<Duration>,
+    pub(crate) retry_window: Option<RetryWindow>,
+    pub(crate) fit_to_max_elapsed: bool,
+    #[cfg(feature = "jitter")]
+    pub(crate) jitter: Option<Jitter>,
+    #[cfg(feature = "jitter")]
+    pub(crate) jitter_seed: Option<u64>,
+}
+
+impl BackoffSettings {
+    /// Return the configuration of an error type.
+    pub(crate) fn for_type<E: Retryable + ?Sized>() -> Self {
+        let initial_delay = match E::max_elapsed().filter(|_| E::fit_to_max_elapsed()) {
+            Some(max_elapsed) => fitted_initial_delay(
+                max_elapsed,
+                effective_max_retries::<E>(),
+                E::backoff_multiplier(),
+            ),
+            None => E::default_initial_delay(),
+        };
+
+        Self {
+            initial_delay,
+            max_retries: effective_max_retries::<E>(),
+            max_elapsed: E::max_elapsed(),
+            multiplier: E::backoff_multiplier(),
+            next_delay: Some(E::next_delay),
+            min_delay: E::min_delay(),
+            default_is_break: E::default_is_break(),
+            quantum: E::quantize_delay(),
+            retry_window: E::retry_window(),
+            fit_to_max_elapsed: E::fit_to_max_elapsed(),
+            #[cfg(feature = "jitter")]
+            jitter: E::jitter(),
+            #[cfg(feature = "jitter")]
+            jitter_seed: E::jitter_seed(),
+        }
+    }
+}
+
+/// Round a delay up to the nearest multiple of the quantum (saturating instead
+/// of overflowing).
+fn quantize(delay: Duration, quantum: Duration) -> Duration {
+    let quantum = quantum.as_nanos();
+
+    if quantum == 0 {
+        delay
+    } else {
+        let nanos = delay.as_nanos().div_ceil(quantum) * quantum;
+
+        u64::try_from(nanos).map_or(Duration::MAX, Duration::from_nanos)
+    }
+}
+
+/// Return the initial delay for which the given number of delays (each the
+/// previous one scaled by the multiplier) has the given sum.
+///
+/// Since the scaled delays are rounded down, their actual sum never exceeds
+/// the total.
+fn fitted_initial_delay(total: Duration, retries: u32, multiplier: f64) -> Duration {
+    let mut factor = 0.0;
+    let mut term = 1.0;
+
+    for _ in 0..retries {
+        factor += term;
+        term *= multiplier;
+    }
+
+    if factor > 0.0 && factor.is_finite() {
+        Duration::from_nanos((total.as_nanos() as f64 / factor) as u64)
+    } else {
+        total
+    }
+}
+
+/// Multiply a delay, saturating instead of overflowing.
+///
+/// This works with nanoseconds (instead of using `Duration::mul_f64`) so that
+/// the default doubling is exact.
+fn scale_delay(delay: Duration, multiplier: f64) -> Duration {
+    // Float to integer casts saturate (and invalid multipliers result in zero).
+    Duration::from_nanos((delay.as_nanos() as f64 * multiplier) as u64)
+}
+
+/// A backoff strategy that uses a function to determine the policy for each
+/// error, falling back to the default delays.
+struct PolicyBackoff<E, C> {
+    policy: C,
+    backoff: ErrorBackoff<E>,
+}
+
+impl<'a, E, C> BackoffStrategy<'a, E> for PolicyBackoff<E, C>
+where
+    E: Retryable,
+    C: FnMut(&E) -> Option<RetryPolicy>,
+{
+    type Output = RetryPolicy;
+
+    fn delay(&mut self, attempt: u32, error: &'a E) -> RetryPolicy {
+        let custom = (self.policy)(error);
+
+        at_least_min_retry_delay(self.backoff.resolve_for(attempt, error, custom))
+    }
+}
+
+/// A backoff strategy whose policies are passed through a final override.
+struct OverrideBackoff<E, O> {
+    override_fn: O,
+    backoff: ErrorBackoff<E>,
+}
+
+impl<'a, E, O> BackoffStrategy<'a, E> for OverrideBackoff<E, O>
+where
+    E: Retryable,
+    O: FnMut(u32, &E, RetryPolicy) -> RetryPolicy,
+{
+    type Output = RetryPolicy;
+
+    fn delay(&mut self, attempt: u32, error: &'a E) -> RetryPolicy {
+        let policy = self
+            .backoff
+            .resolve_for(attempt, error, error.custom_retry_policy());
+
+        if retries_enabled() {
+            at_least_min_retry_delay((self.override_fn)(attempt, error, policy))
+        } else {
+            policy
+        }
+    }
+}
+
+/// The `Retryable` trait allows an error type to define retry logic for
+/// specific errors.
+///
+/// By default, errors without a custom policy are retried with exponential
+/// backoff. Error types that should only retry what they explicitly classify
+/// can stop at unclassified errors instead (see `default_is_break`).
+pub trait Retryable {
+    /// Return the maximum number of retries.
+    ///
+    /// By default this doesn't include the first attempt, so an operation
+    /// that always fails will be called `max_retries() + 1` times (see
+    /// `count_first_attempt`).
+    fn max_retries() -> u32;
+
+    /// Indicate whether the first attempt counts against `max_retries`.
+    ///
+    /// If `true`, `max_retries` is the total number of calls (with a minimum
+    /// of one, unless `on_not_attempted` provides an error). Defaults to
+    /// `false`.
+    fn count_first_attempt() -> bool {
+        false
+    }
+
+    /// Return the error for an operation that the retry configuration
+    /// doesn't allow to be attempted at all.
+    ///
+    /// If this is provided, the operation isn't attempted (and this error is
+    /// returned instead) when `count_first_attempt` is `true` and the
+    /// maximum number of retries is zero, or when the loop starts while
+    /// retries are disabled by the kill switch (see `set_retries_enabled`).
+    /// Otherwise the operation is always attempted once. This applies to
+    /// every retry loop in this crate (including `RetrySession::run`, whose
+    /// steps otherwise always make an attempt) except for `retry_future`.
+    /// Defaults to an empty value.
+    fn on_not_attempted() -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Return the default initial delay.
+    ///
+    /// A zero delay is allowed and means that retries happen immediately, but
+    /// the retry loops will yield to the executor before each retry in this
+    /// case (see `MIN_RETRY_DELAY`).
+    fn default_initial_delay() -> Duration;
+
+    /// Return the log level for this error type (an empty value indicates that
+    /// no logging will be done).
+    fn log_level() -> Option<Level>;
+
+    /// Return the log levels for successive retries, so that the severity can
+    /// escalate as retries pile up.
+    ///
+    /// The first level is used for the first retry, the second for the
+    /// second, and so on, with the last level used for every retry after that
+    /// (so an empty slice disables logging). An empty value (the default)
+    /// uses `log_level` for every retry. This only applies to the messages
+    /// about each retry (and the message about giving up in log-once mode).
+    fn escalating_log_levels() -> Option<&'static [Level]> {
+        None
+    }
+
+    /// Indicate whether retry log messages should include the time elapsed
+    /// since the retry configuration was created.
+    ///
+    /// Defaults to `false`, so that the message format doesn't change for
+    /// anyone parsing logs.
+    fn log_elapsed() -> bool {
+        false
+    }
+
+    /// Indicate whether to log only once for each retry loop.
+    ///
+    /// If `true`, the first retry is logged with a description of the loop,
+    /// subsequent retries aren't logged, and a message is logged if the loop
+    /// gives up. Defaults to `false`.
+    fn log_once() -> bool {
+        false
+    }
+
+    /// Indicate whether log messages include the `Debug` representation of
+    /// the error.
+    ///
+    /// This can be disabled for errors that contain sensitive data, in which
+    /// case messages only describe the attempt and the delay. Defaults to
+    /// `true`.
+    fn log_error_value() -> bool {
+        true
+    }
+
+    /// Return the number of earlier errors that `retry_future_detailed`
+    /// includes (as `Debug` strings) in its failure. Defaults to `3`.
+    fn max_recent_errors() -> usize {
+        3
+    }
+
+    /// Return the maximum number of records that `retry_future_with_history`
+    /// keeps.
+    ///
+    /// Once the limit is reached, the oldest record is dropped for each new
+    /// one, so that a loop with a very large number of retries can't use an
+    /// unbounded amount of memory. Defaults to `128`.
+    fn max_history() -> usize {
+        128
+    }
+
+    /// Return the strategy that `retry_future_detailed` uses to choose which
+    /// error to return when it gives up. Defaults to `ReportStrategy::Last`.
+    fn report_error() -> ReportStrategy {
+        ReportStrategy::Last
+    }
+
+    /// Return the maximum total time for the retry loop.
+    ///
+    /// The loop will stop retrying instead of waiting for a delay that would
+    /// take it past this limit. An empty value (the default) indicates that
+    /// there is no limit.
+    fn max_elapsed() -> Option<Duration> {
+        None
+    }
+
+    /// Indicate whether the default initial delay should be chosen so that
+    /// the default delays for every retry add up to the maximum elapsed time.
+    ///
+    /// The delays still grow by the multiplier, but instead of the loop
+    /// stopping early when the next delay would pass the limit, the attempts
+    /// are spread across the whole budget. Since the time taken by the
+    /// attempts themselves isn't known in advance, a default delay that would
+    /// pass the limit is shortened to the time that remains. The minimum and
+    /// maximum delays (and any jitter or quantization) are applied on top of
+    /// the computed schedule. This has no effect if there is no maximum
+    /// elapsed time. Defaults to `false`.
+    fn fit_to_max_elapsed() -> bool {
+        false
+    }
+
+    /// Return the factor that each default delay is multiplied by to get the
+    /// next one.
+    ///
+    /// Defaults to `2.0`. Values below `1.0` result in delays that decrease
+    /// (which can be useful if confidence grows with each attempt), and
+    /// should generally be used with `min_delay`, so that the delays don't
+    /// shrink to zero.
+    fn backoff_multiplier() -> f64 {
+        2.0
+    }
+
+    /// Return the default delay that follows the given one, which was the
+    /// default delay for the given retry attempt (starting at 1).
+    ///
+    /// This allows any growth curve (such as a Fibonacci sequence or a table
+    /// of delays), with the minimum and maximum delays (and any jitter or
+    /// quantization) still applied to the result. The attempt counts every
+    /// retry, including ones with custom policies, which don't advance the
+    /// default delays. Fitting the delays to the maximum elapsed time assumes
+    /// that they grow by the multiplier. Defaults to multiplying the delay by
+    /// `backoff_multiplier`.
+    fn next_delay(_attempt: u32, previous: Duration) -> Duration {
+        scale_delay(previous, Self::backoff_multiplier())
+    }
+
+    /// Return the minimum default delay.
+    ///
+    /// An empty value (the default) indicates that there is no minimum.
+    fn min_delay() -> Option<Duration> {
+        None
+    }
+
+    /// Return the maximum default delay.
+    ///
+    /// Default delays keep growing exponentially, but the delays that are
+    /// actually used will never be longer than this value. Custom delays are
+    /// not affected. An empty value (the default) indicates that there is no
+    /// limit.
+    fn max_delay() -> Option<Duration> {
+        None
+    }
+
+    /// Return the quantum that default delays are rounded up to a multiple of.
+    ///
+    /// Delays on a coarse grid (such as the nearest 100 milliseconds) allow
+    /// the timer to batch wakeups when many loops are retrying at once. The
+    /// rounding is applied after any jitter and before the maximum delay, and
+    /// custom delays are not affected. An empty value (the default) leaves the
+    /// delays exact.
+    fn quantize_delay() -> Option<Duration> {
+        None
+    }
+
+    /// Return the window of the day in which failures may be retried.
+    ///
+    /// The window is in UTC (see `RetryWindow`). This is useful for scheduled
+    /// jobs that shouldn't add load to a backend during business hours.
+    /// Outside the window, every failure stops the loop, whatever its policy.
+    /// An empty value (the default) allows retries at any time.
+    fn retry_window() -> Option<RetryWindow> {
+        None
+    }
+
+    /// Return the jitter strategy for default delays.
+    ///
+    /// Jitter is applied to each default delay before it is capped by
+    /// `max_delay_at`, and doesn't affect how the following default delays
+    /// grow. An empty value (the default) indicates that there is no jitter.
+    #[cfg(feature = "jitter")]
+    fn jitter() -> Option<Jitter> {
+        None
+    }
+
+    /// Return a seed for the random number generator used for jitter.
+    ///
+    /// By default (with an empty value) every backoff strategy uses entropy,
+    /// which is what jitter is for: clients that fail together retry at
+    /// different times. A pinned seed makes the delays reproducible (for
+    /// example in chaos testing), and a seed derived from something like a
+    /// node ID gives correlated jitter, but clients that share a seed will
+    /// retry in lockstep, so pinning reduces the desynchronization benefit.
+    #[cfg(feature = "jitter")]
+    fn jitter_seed() -> Option<u64> {
+        None
+    }
+
+    /// Indicate whether the retry loop should yield to the executor before each
+    /// retried attempt.
+    ///
+    /// This lets other tasks make progress between attempts on a
+    /// single-threaded executor when the operation is CPU-bound and the delays
+    /// are very short, at the cost of a small amount of extra latency for each
+    /// retry. Waiting for a delay on the Tokio timer already yields, and so
+    /// does a retry after a zero delay, so this mainly matters for loops that
+    /// don't wait, such as `retry_future_dry_run`. Defaults to `false`.
+    fn yield_between_attempts() -> bool {
+        false
+    }
+
+    /// Indicate whether the retry loop should consume a unit of the Tokio
+    /// task budget before each retried attempt.
+    ///
+    /// This uses `tokio::task::consume_budget`, which only yields once the
+    /// task has used up its budget (so unlike `yield_between_attempts`, it
+    /// usually doesn't add any latency). It only has an effect on a Tokio
+    /// runtime. Waiting for a delay on the Tokio timer already consumes a unit
+    /// of the budget, so this mainly matters for loops that don't wait, such
+    /// as `retry_future_dry_run`. Defaults to `false`.
+    fn cooperate() -> bool {
+        false
+    }
+
+    /// Return a retry policy for the given error value.
+    ///
+    /// An empty value represents the default (see `default_is_break`).
+    fn custom_retry_policy(&self) -> Option<RetryPolicy>;
+
+    /// Indicate whether errors without a custom policy stop the loop.
+    ///
+    /// If `true`, the default is `Break` instead of the exponential backoff,
+    /// so that only errors with a custom `Delay` policy are retried. Defaults
+    /// to `false`.
+    fn default_is_break() -> bool {
+        false
+    }
+
+    /// Return a new base for the default delays that follow this error.
+    ///
+    /// If this returns a value, the next default delay will be this value
+    /// (and will grow from there), instead of continuing from the current
+    /// default delay. This is applied after the policy for the error is
+    /// resolved, so for example an error carrying a rate limit reset time can
+    /// wait until the reset with its custom policy (see `delay_until`), while
+    /// making sure that subsequent transient errors don't restart the backoff
+    /// at the initial delay. Defaults to an empty value.
+    fn seed_delay(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Return the maximum default delay for the given retry attempt (which
+    /// starts at 1 for the first retry) after this error.
+    ///
+    /// This allows a schedule of caps (for example one second for the first
+    /// few retries, and ten seconds after that). Defaults to `max_delay`.
+    fn max_delay_at(&self, _attempt: u32) -> Option<Duration> {
+        Self::max_delay()
+    }
+
+    /// Return a key identifying the kind of this error, which is kept so that
+    /// the policy for the next error can depend on it (see
+    /// `policy_with_prev`). Defaults to an empty value.
+    fn error_key(&self) -> Option<String> {
+        None
+    }
+
+    /// Return a retry policy for this error, given the key of the previous
+    /// error in the loop (if there was one and it had a key) and the retry
+    /// attempt (which starts at 1).
+    ///
+    /// This allows rules based on transitions (for example stopping when a
+    /// timeout is followed by a server error). If this returns a value, it
+    /// takes precedence over `custom_retry_policy`. Defaults to an empty
+    /// value.
+    fn policy_with_prev(&self, _prev: Option<&str>, _attempt: u32) -> Option<RetryPolicy> {
+        None
+    }
+
+    /// Return a retry policy for the final retry after this error.
+    ///
+    /// This is consulted instead of the other policies when the retry would be
+    /// the last one that the loop allows (according to `max_retries`, or a
+    /// lower limit such as a `RetrySession`'s), which supports patterns like
+    /// retrying quickly a few times and then making one last attempt after a
+    /// much longer delay. The delay is still subject to the maximum elapsed
+    /// time. An empty value (the default) leaves the final retry to the usual
+    /// policies.
+    fn final_attempt_policy(&self) -> Option<RetryPolicy> {
+        None
+    }
+
+    /// Return the key of the `RetrySession` budget that retries after this
+    /// error use up.
+    ///
+    /// Errors with the same key share the retries that the session allows
+    /// for it (see `RetrySession::key_budget`) across all of its steps, so
+    /// that an error like an authentication failure that recurs at every step
+    /// isn't retried afresh each time. An empty value (the default), or a
+    /// key without a budget in the session, only limits the retries in the
+    /// usual ways.
+    fn session_budget_key(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Generate a new backoff strategy instance.
+    fn new_backoff() -> ErrorBackoff<Self> {
+        ErrorBackoff::from_settings(BackoffSettings::for_type::<Self>())
+    }
+
+    /// Generate a new retry configuration instance.
+    ///
+    /// Elapsed time is measured from when the configuration is created, so a
+    /// new configuration should be used for each retry loop. The retry
+    /// functions in this crate (other than `retry_future`) create it when the
+    /// loop is first polled, just before the first attempt.
+    fn retry_config() -> RetryFutureConfig<ErrorBackoff<Self>, LogOnRetry> {
+        config_with_backoff::<Self, _>(Self::new_backoff())
+    }
+
+    /// Generate a new retry configuration instance that retries immediately
+    /// after the first failure, and then backs off as usual (see
+    /// `ErrorBackoff::quick_then_backoff`).
+    fn quick_then_backoff_config() -> RetryFutureConfig<ErrorBackoff<Self>, LogOnRetry> {
+        config_with_backoff::<Self, _>(ErrorBackoff::quick_then_backoff())
+    }
+
+    /// Generate a new retry configuration instance with the given retry
+    /// handler in place of the default logging.
+    fn retry_config_with_on_retry<R: OnRetry<Self>>(
+        on_retry: R,
+    ) -> RetryFutureConfig<ErrorBackoff<Self>, R>
+    where
+        Self: Sized,
+    {
+        RetryFutureConfig::new(effective_max_retries::<Self>())
+            .on_retry(on_retry)
+            .custom_backoff(Self::new_backoff())
+    }
+}
+
+/// Return the number of retries after the first attempt for an error type.
+pub(crate) fn effective_max_retries<E: Retryable + ?Sized>() -> u32 {
+    retries_after_first::<E>(E::max_retries())
+}
+
+/// Interpret a maximum number of retries for an error type, returning the
+/// number of retries after the first attempt.
+fn retries_after_first<E: Retryable + ?Sized>(max_retries: u32) -> u32 {
+    if E::count_first_attempt() {
+        max_retries.saturating_sub(1)
+    } else {
+        max_retries
+    }
+}
+
+/// Build the retry configuration for an error type with the given backoff.
+fn config_with_backoff<E: Retryable + ?Sized, B>(backoff: B) -> RetryFutureConfig<B, LogOnRetry> {
+    RetryFutureConfig::new(effective_max_retries::<E>())
+        .on_retry(LogOnRetry::new::<E>())
+        .custom_backoff(backoff)
+}
+
+/// A simpler alternative to `Retryable` where the configuration is provided by
+/// associated constants.
+///
+/// Every `SimpleRetryable` type is `Retryable`, and since the configuration
+/// values are known at compile time, building the retry configuration involves
+/// no calls that can't be inlined.
+///
+/// Only the most common options have constants here. Since `Retryable` is
+/// implemented for every `SimpleRetryable` type by a blanket implementation,
+/// the other `Retryable` options can't be overridden for these types, and
+/// always have their defaults. A type that needs any of them should implement
+/// `Retryable` directly instead.
+pub trait SimpleRetryable {
+    /// The maximum number of retries.
+    const MAX_RETRIES: u32;
+
+    /// The default initial delay in milliseconds.
+    const INITIAL_DELAY_MS: u64;
+
+    /// The log level for this error type (an empty value indicates that no
+    /// logging will be done).
+    const LOG_LEVEL: Option<Level> = None;
+
+    /// The factor that each default delay is multiplied by to get the next
+    /// one (see `Retryable::backoff_multiplier`).
+    const BACKOFF_MULTIPLIER: f64 = 2.0;
+
+    /// The maximum default delay in milliseconds (see `Retryable::max_delay`).
+    const MAX_DELAY_MS: Option<u64> = None;
+
+    /// The maximum total time in milliseconds that a retry loop may take (see
+    /// `Retryable::max_elapsed`).
+    const MAX_ELAPSED_MS: Option<u64> = None;
+
+    /// The jitter strategy for the default delays (see `Retryable::jitter`).
+    #[cfg(feature = "jitter")]
+    const JITTER: Option<Jitter> = None;
+
+    /// Whether errors without a retry policy stop the loop instead of getting
+    /// the default delays (see `Retryable::default_is_break`).
+    const DEFAULT_IS_BREAK: bool = false;
+
+    /// Return a retry policy for the given error value.
+    ///
+    /// An empty value represents the default (which is also the default
+    /// implementation).
+    fn retry_policy(&self) -> Option<RetryPolicy> {
+        None
+    }
+}
+
+impl<E: SimpleRetryable> Retryable for E {
+    #[inline]
+    fn max_retries() -> u32 {
+        E::MAX_RETRIES
+    }
+
+    #[inline]
+    fn default_initial_delay() -> Duration {
+        Duration::from_millis(E::INITIAL_DELAY_MS)
+    }
+
+    #[inline]
+    fn log_level() -> Option<Level> {
+        E::LOG_LEVEL
+    }
+
+    #[inline]
+    fn backoff_multiplier() -> f64 {
+        E::BACKOFF_MULTIPLIER
+    }
+
+    #[inline]
+    fn max_delay() -> Option<Duration> {
+        E::MAX_DELAY_MS.map(Duration::from_millis)
+    }
+
+    #[inline]
+    fn max_elapsed() -> Option<Duration> {
+        E::MAX_ELAPSED_MS.map(Duration::from_millis)
+    }
+
+    #[cfg(feature = "jitter")]
+    #[inline]
+    fn jitter() -> Option<Jitter> {
+        E::JITTER
+    }
+
+    #[inline]
+    fn default_is_break() -> bool {
+        E::DEFAULT_IS_BREAK
+    }
+
+    #[inline]
+    fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+        self.retry_policy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::SingleCallError;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    #[derive(thiserror::Error, Debug, Eq, PartialEq)]
+    pub enum Error {
+        #[error("Error A")]
+        A(String),
+        #[error("Error B")]
+        B(u64),
+        #[error("Error C")]
+        C,
+    }
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            7
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(250)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            match self {
+                Error::A(_) => Some(RetryPolicy::Delay(Duration::from_millis(100))),
+                Error::B(_) => Some(RetryPolicy::Break),
+                Error::C => None,
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct ZeroDelayError;
+
+    impl Retryable for ZeroDelayError {
+        fn max_retries() -> u32 {
+            1000
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::ZERO
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct SimpleError(bool);
+
+    impl SimpleRetryable for SimpleError {
+        const MAX_RETRIES: u32 = 3;
+        const INITIAL_DELAY_MS: u64 = 1;
+
+        fn retry_policy(&self) -> Option<RetryPolicy> {
+            if self.0 {
+                None
+            } else {
+                Some(RetryPolicy::Break)
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct CappedSimpleError;
+
+    impl SimpleRetryable for CappedSimpleError {
+        const MAX_RETRIES: u32 = 10;
+        const INITIAL_DELAY_MS: u64 = 100;
+        const BACKOFF_MULTIPLIER: f64 = 3.0;
+        const MAX_DELAY_MS: Option<u64> = Some(1000);
+        const MAX_ELAPSED_MS: Option<u64> = Some(5000);
+    }
+
+    #[derive(Debug)]
+    pub struct ElapsedError;
+
+    impl Retryable for ElapsedError {
+        fn max_retries() -> u32 {
+            10
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn log_elapsed() -> bool {
+            true
+        }
+
+        fn max_elapsed() -> Option<Duration> {
+            Some(Duration::from_secs(1))
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(300)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct CountedError;
+
+    impl Retryable for CountedError {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn count_first_attempt() -> bool {
+            true
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(1)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct CappedError;
+
+    impl Retryable for CappedError {
+        fn max_retries() -> u32 {
+            6
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(400)
+        }
+
+        fn max_delay_at(&self, attempt: u32) -> Option<Duration> {
+            if attempt <= 3 {
+                Some(Duration::from_secs(1))
+            } else {
+                Some(Duration::from_secs(10))
+            }
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct SpeedUpError;
+
+    impl Retryable for SpeedUpError {
+        fn max_retries() -> u32 {
+            6
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(800)
+        }
+
+        fn backoff_multiplier() -> f64 {
+            0.5
+        }
+
+        fn min_delay() -> Option<Duration> {
+            Some(Duration::from_millis(150))
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct LimitedError;
+
+    impl Retryable for LimitedError {
+        fn max_retries() -> u32 {
+            5
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn max_delay() -> Option<Duration> {
+            Some(Duration::from_millis(300))
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct QuantizedError;
+
+    impl Retryable for QuantizedError {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(137)
+        }
+
+        fn quantize_delay() -> Option<Duration> {
+            Some(Duration::from_millis(50))
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    /// The mocked time since midnight UTC for `WindowedError`, in hours.
+    static WINDOW_CLOCK_HOURS: AtomicU64 = AtomicU64::new(0);
+
+    #[derive(Debug)]
+    pub struct WindowedError;
+
+    impl Retryable for WindowedError {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn retry_window() -> Option<RetryWindow> {
+            let hours = |hours: u64| Duration::from_secs(hours * 60 * 60);
+
+            Some(RetryWindow::utc(hours(9), hours(17)).with_clock(|| {
+                Duration::from_secs(WINDOW_CLOCK_HOURS.load(Ordering::SeqCst) * 60 * 60)
+            }))
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            Some(RetryPolicy::Delay(Duration::from_millis(10)))
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct FittedError<const N: u32>;
+
+    impl<const N: u32> Retryable for FittedError<N> {
+        fn max_retries() -> u32 {
+            N
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_secs(60)
+        }
+
+        fn max_elapsed() -> Option<Duration> {
+            Some(Duration::from_secs(10))
+        }
+
+        fn fit_to_max_elapsed() -> bool {
+            true
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn success() {
+        let counter = Mutex::new(0);
+
+        let future = || async {
+            let mut c = counter.lock().await;
+            *c += 1;
+
+            if *c < 5 {
+                Err(Error::A("baz".to_string()))
+            } else {
+                Ok("foo")
+            }
+        };
+
+        assert_eq!(retry_future(future).await.unwrap(), "foo");
+    }
+
+    #[tokio::test]
+    async fn failure() {
+        let counter = Mutex::new(0);
+
+        let future = || async {
+            let mut c = counter.lock().await;
+            *c += 1;
+
+            let result: Result<&'static str, Error> = if *c < 5 {
+                Err(Error::A("baz".to_string()))
+            } else {
+                Err(Error::B(123))
+            };
+
+            result
+        };
+
+        assert_eq!(retry_future(future).await, Err(Error::B(123)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn attempt() {
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(LimitedError)
+            } else {
+                Ok("foo")
+            }
+        };
+
+        let (value, attempt) = retry_future_attempt(future).await.unwrap();
+        assert_eq!(value, "foo");
+        assert_eq!(attempt, 3);
+        assert_eq!(attempts.load(Ordering::SeqCst), attempt);
+
+        assert_eq!(
+            retry_future_attempt(|| async { Ok::<_, LimitedError>(()) })
+                .await
+                .unwrap(),
+            ((), 1)
+        );
+    }
+
+    // On a paused clock, a zero sleep only yields once the task budget runs
+    // out.
+    #[tokio::test(start_paused = true)]
+    async fn zero_initial_delay_yields() {
+        let ready = Arc::new(AtomicBool::new(false));
+        let attempts = AtomicU32::new(0);
+
+        tokio::spawn({
+            let ready = ready.clone();
+            async move { ready.store(true, Ordering::SeqCst) }
+        });
+
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+
+            if ready.load(Ordering::SeqCst) {
+                Ok(())
+            } else {
+                Err(ZeroDelayError)
+            }
+        };
+
+        assert!(retry_future(future).await.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[derive(Debug)]
+    pub struct CappedToZeroError;
+
+    impl Retryable for CappedToZeroError {
+        fn max_retries() -> u32 {
+            1000
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn max_delay() -> Option<Duration> {
+            Some(Duration::ZERO)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn zero_effective_delay_yields() {
+        let ready = Arc::new(AtomicBool::new(false));
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+
+            if ready.load(Ordering::SeqCst) {
+                Ok(())
+            } else {
+                Err(CappedToZeroError)
+            }
+        };
+
+        // The initial delay isn't zero, but the capped delays are.
+        tokio::spawn({
+            let ready = ready.clone();
+            async move { ready.store(true, Ordering::SeqCst) }
+        });
+
+        assert!(retry_future(future).await.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        ready.store(false, Ordering::SeqCst);
+        attempts.store(0, Ordering::SeqCst);
+
+        tokio::spawn({
+            let ready = ready.clone();
+            async move { ready.store(true, Ordering::SeqCst) }
+        });
+
+        assert!(retry_operation(future).await.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[derive(Debug)]
+    pub struct CooperativeError;
+
+    impl Retryable for CooperativeError {
+        fn max_retries() -> u32 {
+            1000
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn cooperate() -> bool {
+            true
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(10)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            Some(RetryPolicy::Delay(Duration::ZERO))
+        }
+    }
+
+    #[tokio::test]
+    async fn cooperate() {
+        let ready = Arc::new(AtomicBool::new(false));
+        let attempts = AtomicU32::new(0);
+
+        tokio::spawn({
+            let ready = ready.clone();
+            async move { ready.store(true, Ordering::SeqCst) }
+        });
+
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+
+            if ready.load(Ordering::SeqCst) {
+                Ok(())
+            } else {
+                Err(CooperativeError)
+            }
+        };
+
+        assert!(retry_future(future).await.is_ok());
+        assert!(attempts.load(Ordering::SeqCst) < 1000);
+
+        // The dry run never sleeps, so only the budget lets the task run.
+        ready.store(false, Ordering::SeqCst);
+        attempts.store(0, Ordering::SeqCst);
+
+        tokio::spawn({
+            let ready = ready.clone();
+            async move { ready.store(true, Ordering::SeqCst) }
+        });
+
+        assert!(retry_future_dry_run(future).await.is_ok());
+        assert!(attempts.load(Ordering::SeqCst) < 1000);
+    }
+
+    #[tokio::test]
+    async fn simple_retryable() {
+        let attempts = AtomicU32::new(0);
+
+        let future = || async {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            let result: Result<(), SimpleError> = Err(SimpleError(attempt < 1));
+
+            result
+        };
+
+        assert_eq!(retry_future(future).await, Err(SimpleError(false)));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(SimpleError::max_retries(), 3);
+        assert_eq!(
+            SimpleError::default_initial_delay(),
+            Duration::from_millis(1)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn simple_retryable_options() {
+        let started = Instant::now();
+        let future = || async { Err::<(), _>(CappedSimpleError) };
+
+        assert!(retry_future(future).await.is_err());
+
+        // The delays are 100, 300, 900, and then 1000 milliseconds, until the
+        // next would take the loop past five seconds.
+        assert_eq!(started.elapsed(), Duration::from_millis(4300));
+        assert!(!CappedSimpleError::default_is_break());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn max_elapsed() {
+        let attempts = AtomicU32::new(0);
+        let started = Instant::now();
+
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            let result: Result<(), ElapsedError> = Err(ElapsedError);
+
+            result
+        };
+
+        assert!(retry_future(future).await.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(started.elapsed(), Duration::from_millis(900));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn max_elapsed_from_first_attempt() {
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(ElapsedError)
+        };
+
+        // The loops are created more than the maximum elapsed time before they
+        // start.
+        let with_backoff = retry_future_with_backoff(future, ElapsedError::new_backoff());
+        let detailed = retry_future_detailed(None, future);
+        let plain = retry_future(future);
+        tokio::time::advance(Duration::from_secs(2)).await;
+
+        let started = Instant::now();
+        assert!(with_backoff.await.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(started.elapsed(), Duration::from_millis(900));
+
+        attempts.store(0, Ordering::SeqCst);
+        assert_eq!(detailed.await.unwrap_err().stats.attempts, 3);
+
+        attempts.store(0, Ordering::SeqCst);
+        let started = Instant::now();
+        assert!(plain.await.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(started.elapsed(), Duration::from_millis(900));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn log_elapsed() {
+        let log_on_retry = LogOnRetry::new::<ElapsedError>();
+        tokio::time::advance(Duration::from_secs(5)).await;
+
+        assert_eq!(
+            log_on_retry.message(2, Duration::from_millis(600), &ElapsedError),
+            "Retry 2; waiting 600ms (5s elapsed) after error: ElapsedError"
+        );
+        assert_eq!(
+            LogOnRetry::new::<Error>().message(1, Duration::from_millis(100), &Error::B(1)),
+            "Retry 1; waiting 100ms after error: B(1)"
+        );
+    }
+
+    #[test]
+    fn named_messages() {
+        assert_eq!(
+            LogOnRetry::new::<Error>().with_name("fetch-user").message(
+                1,
+                Duration::from_millis(100),
+                &Error::B(1)
+            ),
+            "fetch-user: Retry 1; waiting 100ms after error: B(1)"
+        );
+    }
+
+    #[derive(Debug)]
+    pub struct OnceError;
+
+    impl Retryable for OnceError {
+        fn max_retries() -> u32 {
+            4
+        }
+
+        fn log_level() -> Option<log::Level> {
+            Some(Level::Warn)
+        }
+
+        fn log_once() -> bool {
+            true
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn worst_case_total_delay() {
+        assert_eq!(
+            super::worst_case_total_delay::<LimitedError>(),
+            Duration::from_millis(100 + 200 + 300 * 3)
+        );
+        assert_eq!(
+            super::worst_case_total_delay::<Error>(),
+            Duration::from_millis(250 * 127)
+        );
+        assert_eq!(
+            super::worst_case_total_delay::<SpeedUpError>(),
+            Duration::from_millis(800 + 400 + 200 + 150 * 3)
+        );
+        assert_eq!(
+            super::worst_case_total_delay::<ElapsedError>(),
+            Duration::from_secs(1)
+        );
+
+        // This doesn't iterate over every retry.
+        assert_eq!(
+            super::worst_case_total_delay_with_retries::<LimitedError>(u32::MAX),
+            Duration::from_millis(100 + 200 + 300 * (u64::from(u32::MAX) - 2))
+        );
+
+        let started = Instant::now();
+        let future = || async { Err::<(), _>(LimitedError) };
+        assert!(retry_future(future).await.is_err());
+        assert_eq!(
+            started.elapsed(),
+            super::worst_case_total_delay::<LimitedError>()
+        );
+    }
+
+    #[derive(Debug)]
+    pub struct SensitiveError(&'static str);
+
+    impl Retryable for SensitiveError {
+        fn max_retries() -> u32 {
+            2
+        }
+
+        fn log_level() -> Option<log::Level> {
+            Some(Level::Warn)
+        }
+
+        fn log_error_value() -> bool {
+            false
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[test]
+    fn log_error_value() {
+        let error = SensitiveError("jane@example.com");
+        let message =
+            LogOnRetry::new::<SensitiveError>().message(1, Duration::from_millis(100), &error);
+
+        assert_eq!(message, "Retry 1; waiting 100ms");
+        assert!(!message.contains(error.0));
+    }
+
+    #[test]
+    fn log_once() {
+        let log_on_retry = LogOnRetry::new::<OnceError>();
+
+        assert_eq!(
+            log_on_retry.message(1, Duration::from_millis(100), &OnceError),
+            format!(
+                "Retrying {} with backoff (up to 4 retries); waiting 100ms after error: OnceError",
+                std::any::type_name::<OnceError>()
+            )
+        );
+        assert!(LogOnRetry::new::<Error>().log_once.is_none());
+
+        assert_eq!(
+            LogOnRetry::new::<OnceError>().with_max_retries(2).message(
+                1,
+                Duration::from_millis(100),
+                &OnceError
+            ),
+            format!(
+                "Retrying {} with backoff (up to 2 retries); waiting 100ms after error: OnceError",
+                std::any::type_name::<OnceError>()
+            )
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn with_backoff() {
+        let started = Instant::now();
+        let future = || async { Err::<(), _>(Error::C) };
+
+        let backoff = ErrorBackoff::with_initial_delay(Duration::from_millis(10));
+        assert_eq!(
+            retry_future_with_backoff(future, backoff).await,
+            Err(Error::C)
+        );
+        assert_eq!(started.elapsed(), Duration::from_millis(10 * 127));
+
+        let started = Instant::now();
+        assert_eq!(retry_future(future).await, Err(Error::C));
+        assert_eq!(started.elapsed(), Duration::from_millis(250 * 127));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn with_override() {
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(Error::C)
+        };
+
+        // The override forces a break even though the policy is to retry.
+        let result = retry_future_with_override(future, |attempt, _, policy| {
+            if attempt == 2 {
+                RetryPolicy::Break
+            } else {
+                policy
+            }
+        })
+        .await;
+        assert_eq!(result, Err(Error::C));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        // It also supersedes the error's own `Break`.
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(Error::B(1))
+        };
+        let started = Instant::now();
+        let result = retry_future_with_override(future, |_, _, policy| {
+            assert_eq!(policy, RetryPolicy::Break);
+            RetryPolicy::Delay(Duration::from_millis(1))
+        })
+        .await;
+        assert_eq!(result, Err(Error::B(1)));
+        assert_eq!(attempts.load(Ordering::SeqCst), 8);
+        assert_eq!(started.elapsed(), Duration::from_millis(7));
+    }
+
+    #[test]
+    fn disabled_log_level() {
+        // No logger is installed in tests, so all levels are disabled.
+        let mut log_on_retry = LogOnRetry {
+            level: Some(Level::Error),
+            ..LogOnRetry::new::<Error>()
+        };
+
+        let future = log_on_retry.on_retry(1, Some(Duration::from_millis(100)), &Error::C);
+        assert!(future.message.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drop_cancels() {
+        let resource = Arc::new(());
+        let attempts = AtomicU32::new(0);
+
+        // The operation holds the resource while it fails immediately, and
+        // then while it never completes.
+        let operation = || {
+            let resource = resource.clone();
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+
+            async move {
+                let _resource = resource;
+
+                if attempt > 0 {
+                    std::future::pending::<()>().await;
+                }
+
+                Err::<(), _>(ElapsedError)
+            }
+        };
+
+        // The loop is dropped during the first delay.
+        tokio::select! {
+            _ = retry_future(operation) => panic!("Unexpected completion"),
+            () = tokio::time::sleep(Duration::from_millis(100)) => {}
+        }
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(Arc::strong_count(&resource), 1);
+
+        // The loop is dropped during the second attempt.
+        attempts.store(0, Ordering::SeqCst);
+        tokio::select! {
+            _ = retry_future(operation) => panic!("Unexpected completion"),
+            () = tokio::time::sleep(Duration::from_millis(500)) => {}
+        }
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(Arc::strong_count(&resource), 1);
+
+        // The attempt timeouts are dropped along with the attempts.
+        attempts.store(0, Ordering::SeqCst);
+        let spec = GrowthSpec {
+            initial_delay: Duration::from_millis(100),
+            initial_timeout: Duration::from_secs(60),
+            multiplier: 2.0,
+        };
+        tokio::select! {
+            _ = retry_future_growing(operation, spec) => panic!("Unexpected completion"),
+            () = tokio::time::sleep(Duration::from_millis(500)) => {}
+        }
+        assert_eq!(Arc::strong_count(&resource), 1);
+
+        // Nothing was left running.
+        let metrics = tokio::runtime::Handle::current().metrics();
+        assert_eq!(metrics.num_alive_tasks(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn success_without_delay() {
+        let attempts = AtomicU32::new(0);
+        let started = tokio::time::Instant::now();
+
+        // The second attempt takes longer than the following delay would, and
+        // succeeds.
+        let future = || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                Err(ElapsedError)
+            } else {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                Ok(())
+            }
+        };
+
+        assert_eq!(retry_future_attempt(future).await.unwrap(), ((), 2));
+        assert_eq!(started.elapsed(), Duration::from_millis(800));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn count_first_attempt() {
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(CountedError)
+        };
+
+        assert!(retry_future(future).await.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(Error::C)
+        };
+
+        assert!(retry_future(future).await.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 8);
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub enum StrictError {
+        Classified,
+        Unclassified,
+    }
+
+    impl Retryable for StrictError {
         fn max_retries() -> u32 {
-            7
+            5
         }
 
         fn log_level() -> Option<log::Level> {
@@ -157,52 +2799,555 @@ mod tests {
         }
 
         fn default_initial_delay() -> Duration {
-            Duration::from_millis(250)
+            Duration::from_millis(100)
+        }
+
+        fn default_is_break() -> bool {
+            true
         }
 
         fn custom_retry_policy(&self) -> Option<RetryPolicy> {
             match self {
-                Error::A(_) => Some(RetryPolicy::Delay(Duration::from_millis(100))),
-                Error::B(_) => Some(RetryPolicy::Break),
+                Self::Classified => Some(RetryPolicy::Delay(Duration::from_millis(10))),
+                Self::Unclassified => None,
             }
         }
     }
 
-    #[tokio::test]
-    async fn success() {
-        let counter = Mutex::new(0);
+    #[tokio::test(start_paused = true)]
+    async fn default_is_break() {
+        let mut backoff = StrictError::new_backoff();
+        assert_eq!(
+            backoff.delay(1, &StrictError::Classified),
+            RetryPolicy::Delay(Duration::from_millis(10))
+        );
+        assert_eq!(
+            backoff.delay(2, &StrictError::Unclassified),
+            RetryPolicy::Break
+        );
+        assert_eq!(backoff.stop_reason(), Some(StopReason::Break));
 
-        let future = || async {
-            let mut c = counter.lock().await;
-            *c += 1;
+        // Without the flag, unclassified errors get the default backoff.
+        let mut backoff = Error::new_backoff();
+        assert_eq!(
+            backoff.delay(1, &Error::C),
+            RetryPolicy::Delay(Duration::from_millis(250))
+        );
 
-            if *c < 5 {
-                Err(Error::A("baz".to_string()))
+        let attempts = AtomicU32::new(0);
+        let started = Instant::now();
+        let future = || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err::<(), _>(StrictError::Classified)
             } else {
-                Ok("foo")
+                Err(StrictError::Unclassified)
             }
         };
 
-        assert_eq!(retry_future(future).await.unwrap(), "foo");
+        assert_eq!(retry_future(future).await, Err(StrictError::Unclassified));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(started.elapsed(), Duration::from_millis(20));
+        assert_eq!(
+            super::worst_case_total_delay::<StrictError>(),
+            Duration::ZERO
+        );
     }
 
-    #[tokio::test]
-    async fn failure() {
-        let counter = Mutex::new(0);
+    #[tokio::test(start_paused = true)]
+    async fn not_attempted() {
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(SingleCallError::Failed)
+        };
+
+        assert_eq!(
+            retry_future_with_backoff(future, SingleCallError::new_backoff()).await,
+            Err(SingleCallError::NotAttempted)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 0);
+
+        // The plain `tryhard` loop always makes the first attempt.
+        assert_eq!(retry_future(future).await, Err(SingleCallError::Failed));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        assert_eq!(
+            retry_future_dyn_retries(future, || 2).await,
+            Err(SingleCallError::Failed)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub enum FlappingError {
+        Timeout,
+        ServerError,
+    }
+
+    impl Retryable for FlappingError {
+        fn max_retries() -> u32 {
+            5
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(10)
+        }
+
+        fn error_key(&self) -> Option<String> {
+            Some(format!("{:?}", self))
+        }
+
+        fn policy_with_prev(&self, prev: Option<&str>, _attempt: u32) -> Option<RetryPolicy> {
+            match (prev, self) {
+                (Some("Timeout"), Self::ServerError) => Some(RetryPolicy::Break),
+                _ => None,
+            }
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
 
+    #[tokio::test(start_paused = true)]
+    async fn policy_with_prev() {
+        let attempts = AtomicU32::new(0);
         let future = || async {
-            let mut c = counter.lock().await;
-            *c += 1;
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 => Err::<(), _>(FlappingError::ServerError),
+                1 | 2 => Err(FlappingError::Timeout),
+                _ => Err(FlappingError::ServerError),
+            }
+        };
 
-            let result: Result<&'static str, Error> = if *c < 5 {
-                Err(Error::A("baz".to_string()))
+        assert_eq!(retry_future(future).await, Err(FlappingError::ServerError));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+
+        let mut backoff = FlappingError::new_backoff();
+        backoff.delay(1, &FlappingError::Timeout);
+        backoff.reset();
+        assert_eq!(
+            backoff.delay(1, &FlappingError::ServerError),
+            RetryPolicy::Delay(Duration::from_millis(10))
+        );
+    }
+
+    #[derive(Debug)]
+    pub struct FinalError(bool);
+
+    impl Retryable for FinalError {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+
+        fn final_attempt_policy(&self) -> Option<RetryPolicy> {
+            if self.0 {
+                Some(RetryPolicy::Delay(Duration::from_secs(10)))
             } else {
-                Err(Error::B(123))
-            };
+                None
+            }
+        }
+    }
 
-            result
+    #[tokio::test(start_paused = true)]
+    async fn final_attempt_policy() {
+        for (slow, expected) in [(true, vec![100, 200, 10000]), (false, vec![100, 200, 400])] {
+            let delays = Arc::new(std::sync::Mutex::new(vec![]));
+            let recorded = delays.clone();
+            let result = retry_future_with_on_sleep(
+                || async { Err::<(), _>(FinalError(slow)) },
+                move |delay| recorded.lock().unwrap().push(delay.as_millis()),
+            )
+            .await;
+
+            assert!(result.is_err());
+            assert_eq!(*delays.lock().unwrap(), expected);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn final_attempt_policy_with_lower_max() {
+        let started = Instant::now();
+        let attempts = AtomicU32::new(0);
+        let result = retry_future_dyn_retries(
+            || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(FinalError(true))
+            },
+            || 2,
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(started.elapsed(), Duration::from_millis(10100));
+
+        let started = Instant::now();
+        let attempts = AtomicU32::new(0);
+        let mut session = RetrySession::new().max_retries(2);
+        let result = session
+            .run(|| async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(FinalError(true))
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(started.elapsed(), Duration::from_millis(10100));
+    }
+
+    #[derive(Debug)]
+    pub struct FibonacciError;
+
+    impl Retryable for FibonacciError {
+        fn max_retries() -> u32 {
+            5
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn next_delay(attempt: u32, _previous: Duration) -> Duration {
+            let (mut current, mut next) = (1, 1);
+
+            for _ in 0..attempt {
+                (current, next) = (next, current + next);
+            }
+
+            Duration::from_millis(100) * current
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn next_delay() {
+        let delays = Arc::new(std::sync::Mutex::new(vec![]));
+        let recorded = delays.clone();
+        let result = retry_future_with_on_sleep(
+            || async { Err::<(), _>(FibonacciError) },
+            move |delay| recorded.lock().unwrap().push(delay.as_millis()),
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(*delays.lock().unwrap(), vec![100, 100, 200, 300, 500]);
+        assert_eq!(
+            crate::worst_case_total_delay::<FibonacciError>(),
+            Duration::from_millis(1200)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn quick_then_backoff() {
+        let started = tokio::time::Instant::now();
+        let attempted = std::sync::Mutex::new(vec![]);
+        let future = || async {
+            attempted
+                .lock()
+                .unwrap()
+                .push(started.elapsed().as_millis());
+            Err::<(), _>(FinalError(false))
         };
 
-        assert_eq!(retry_future(future).await, Err(Error::B(123)));
+        assert!(
+            retry_future_with_backoff(future, ErrorBackoff::quick_then_backoff())
+                .await
+                .is_err()
+        );
+        // The immediate retry still waits for a tick of the timer.
+        assert_eq!(*attempted.lock().unwrap(), vec![0, 1, 101, 301]);
+
+        let mut backoff = ErrorBackoff::<FinalError>::quick_then_backoff();
+        assert_eq!(
+            backoff.delay(1, &FinalError(false)),
+            RetryPolicy::Delay(MIN_RETRY_DELAY)
+        );
+        assert_eq!(
+            backoff.delay(2, &FinalError(false)),
+            RetryPolicy::Delay(Duration::from_millis(100))
+        );
+        backoff.reset();
+        assert_eq!(
+            backoff.delay(1, &FinalError(false)),
+            RetryPolicy::Delay(MIN_RETRY_DELAY)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn quantize_delay() {
+        let mut backoff = QuantizedError::new_backoff();
+        let delays = (1..=3)
+            .map(|attempt| backoff.delay(attempt, &QuantizedError))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            delays,
+            vec![
+                RetryPolicy::Delay(Duration::from_millis(150)),
+                RetryPolicy::Delay(Duration::from_millis(300)),
+                RetryPolicy::Delay(Duration::from_millis(550)),
+            ]
+        );
+        assert_eq!(
+            super::worst_case_total_delay::<QuantizedError>(),
+            Duration::from_millis(1000)
+        );
+    }
+
+    fn fitted_schedule<const N: u32>() -> Vec<Duration> {
+        let mut backoff = FittedError::<N>::new_backoff();
+
+        (1..=N)
+            .map(|attempt| match backoff.delay(attempt, &FittedError) {
+                RetryPolicy::Delay(delay) => delay,
+                policy => panic!("Unexpected policy: {:?}", policy),
+            })
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fit_to_max_elapsed() {
+        let budget = Duration::from_secs(10);
+        let schedules = [
+            fitted_schedule::<1>(),
+            fitted_schedule::<2>(),
+            fitted_schedule::<3>(),
+            fitted_schedule::<5>(),
+            fitted_schedule::<8>(),
+        ];
+
+        for schedule in schedules {
+            let total = schedule.iter().sum::<Duration>();
+
+            assert!(total <= budget);
+            assert!(total > budget - Duration::from_millis(1));
+            assert!(schedule.windows(2).all(|pair| pair[1] >= pair[0] * 2));
+        }
+
+        assert_eq!(fitted_schedule::<1>(), vec![budget]);
+        assert!(super::worst_case_total_delay::<FittedError<5>>() <= budget);
+
+        // The last delay is shortened to make up for the time taken by the
+        // attempts.
+        let attempts = AtomicU32::new(0);
+        let started = Instant::now();
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Err::<(), _>(FittedError::<3>)
+        };
+
+        assert!(retry_future(future).await.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+        assert_eq!(started.elapsed(), budget + Duration::from_millis(100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_window() {
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(WindowedError)
+        };
+
+        WINDOW_CLOCK_HOURS.store(12, Ordering::SeqCst);
+        assert!(retry_future(future).await.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+
+        WINDOW_CLOCK_HOURS.store(20, Ordering::SeqCst);
+        assert!(retry_future(future).await.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 5);
+
+        let mut backoff = WindowedError::new_backoff();
+        assert_eq!(backoff.delay(1, &WindowedError), RetryPolicy::Break);
+        assert_eq!(backoff.stop_reason(), Some(StopReason::OutsideWindow));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn peek_delay() {
+        let mut backoff = Error::new_backoff();
+        backoff.delay(1, &Error::C);
+
+        let peeked = backoff.peek_delay(2, &Error::C);
+        assert_eq!(peeked, RetryPolicy::Delay(Duration::from_millis(500)));
+        assert_eq!(backoff.peek_delay(2, &Error::C), peeked);
+        assert_eq!(backoff.peek_delay(2, &Error::B(1)), RetryPolicy::Break);
+        assert_eq!(backoff.stop_reason(), None);
+        assert_eq!(backoff.delay(2, &Error::C), peeked);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn max_delay_at() {
+        let mut backoff = CappedError::new_backoff();
+        let delays = (1..=6)
+            .map(|attempt| backoff.delay(attempt, &CappedError))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            delays,
+            [400, 800, 1000, 3200, 6400, 10000]
+                .into_iter()
+                .map(|ms| RetryPolicy::Delay(Duration::from_millis(ms)))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn decreasing_delays() {
+        let mut backoff = SpeedUpError::new_backoff();
+        let delays = (1..=6)
+            .map(|attempt| backoff.delay(attempt, &SpeedUpError))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            delays,
+            [800, 400, 200, 150, 150, 150]
+                .into_iter()
+                .map(|ms| RetryPolicy::Delay(Duration::from_millis(ms)))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dyn_retries() {
+        let flag = AtomicU32::new(2);
+        let max_retries = || flag.load(Ordering::SeqCst);
+
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(Error::C)
+        };
+
+        assert!(retry_future_dyn_retries(future, max_retries).await.is_err());
+        assert_eq!(attempts.swap(0, Ordering::SeqCst), 3);
+
+        flag.store(4, Ordering::SeqCst);
+
+        assert!(retry_future_dyn_retries(future, max_retries).await.is_err());
+        assert_eq!(attempts.swap(0, Ordering::SeqCst), 5);
+
+        // The value includes the first attempt for this error type.
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(CountedError)
+        };
+
+        assert!(retry_future_dyn_retries(future, || 2).await.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        assert_eq!(
+            worst_case_total_delay_with_retries::<LimitedError>(2),
+            Duration::from_millis(100 + 200)
+        );
+        assert_eq!(
+            worst_case_total_delay_with_retries::<LimitedError>(5),
+            super::worst_case_total_delay::<LimitedError>()
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn on_sleep() {
+        let delays = Arc::new(std::sync::Mutex::new(vec![]));
+        let future = || async { Err::<(), _>(CappedError) };
+
+        let recorded = delays.clone();
+        let result = retry_future_with_on_sleep(future, move |delay| {
+            recorded.lock().unwrap().push(delay.as_millis())
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            *delays.lock().unwrap(),
+            vec![400, 800, 1000, 3200, 6400, 10000]
+        );
+    }
+
+    trait Fetcher {
+        fn fetch(&self) -> Pin<Box<dyn Future<Output = Result<u32, Error>> + Send + '_>>;
+    }
+
+    struct CountingFetcher {
+        attempts: AtomicU32,
+    }
+
+    impl Fetcher for CountingFetcher {
+        fn fetch(&self) -> Pin<Box<dyn Future<Output = Result<u32, Error>> + Send + '_>> {
+            retry_future_boxed(|| async {
+                match self.attempts.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(Error::C),
+                    attempt => Ok(attempt),
+                }
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn boxed_future() {
+        let fetcher: Arc<dyn Fetcher + Send + Sync> = Arc::new(CountingFetcher {
+            attempts: AtomicU32::new(0),
+        });
+
+        let result = tokio::spawn(async move { fetcher.fetch().await }).await;
+
+        assert_eq!(result.unwrap(), Ok(1));
+    }
+
+    #[derive(Clone, Default)]
+    struct CountingOnRetry {
+        delays: Arc<std::sync::Mutex<Vec<Option<Duration>>>>,
+    }
+
+    impl<E> OnRetry<E> for CountingOnRetry {
+        type Future = std::future::Ready<()>;
+
+        fn on_retry(&mut self, _: u32, next_delay: Option<Duration>, _: &E) -> Self::Future {
+            self.delays.lock().unwrap().push(next_delay);
+            std::future::ready(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn custom_on_retry() {
+        let on_retry = CountingOnRetry::default();
+        let future = || async { Err::<(), _>(CountedError) };
+
+        assert!(retry_future_with_on_retry(future, on_retry.clone())
+            .await
+            .is_err());
+        assert_eq!(
+            *on_retry.delays.lock().unwrap(),
+            vec![
+                Some(Duration::from_millis(1)),
+                Some(Duration::from_millis(2)),
+                None
+            ]
+        );
     }
 }
@@ -0,0 +1,135 @@
+//! Limiting the number of concurrent retry loops.
+use crate::{retry_with_defaults, Retryable};
+use std::fmt::{Debug, Display};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// The error returned by `retry_future_limited`.
+#[derive(Debug, Eq, PartialEq)]
+pub enum LimitedError<E> {
+    /// The semaphore was closed, and the operation was not attempted.
+    Closed,
+    /// The retry loop failed with the given error.
+    Failed(E),
+}
+
+impl<E: Display> Display for LimitedError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Closed => write!(f, "Retry semaphore is closed"),
+            Self::Failed(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for LimitedError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Closed => None,
+            Self::Failed(error) => Some(error),
+        }
+    }
+}
+
+/// Execute a future with retries while holding a permit from the given
+/// semaphore.
+///
+/// The permit is acquired before the first attempt and held for the whole
+/// loop, including the sleeps between attempts, so the number of permits
+/// caps the number of operations that can be attempting or waiting to retry
+/// at the same time (across every loop that shares the semaphore). If the
+/// semaphore is closed (including while waiting for a permit), the operation
+/// isn't attempted, and `LimitedError::Closed` is returned.
+pub async fn retry_future_limited<F, Fut, T, E>(
+    f: F,
+    semaphore: Arc<Semaphore>,
+) -> Result<T, LimitedError<E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let _permit = semaphore
+        .acquire_owned()
+        .await
+        .map_err(|_| LimitedError::Closed)?;
+
+    retry_with_defaults(f).await.map_err(LimitedError::Failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestError;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    type Error = TestError<10>;
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrency_cap() {
+        let semaphore = Arc::new(Semaphore::new(2));
+        let active = Arc::new(AtomicU32::new(0));
+        let max_active = Arc::new(AtomicU32::new(0));
+
+        let tasks = (0..10)
+            .map(|_| {
+                let semaphore = semaphore.clone();
+                let active = active.clone();
+                let max_active = max_active.clone();
+
+                tokio::spawn(async move {
+                    let attempts = AtomicU32::new(0);
+                    let future = || async {
+                        let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+
+                        if attempt == 0 {
+                            let now_active = active.fetch_add(1, Ordering::SeqCst) + 1;
+                            max_active.fetch_max(now_active, Ordering::SeqCst);
+                        }
+
+                        if attempt < 2 {
+                            Err(Error::Transient)
+                        } else {
+                            active.fetch_sub(1, Ordering::SeqCst);
+                            Ok(())
+                        }
+                    };
+
+                    retry_future_limited(future, semaphore).await
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), Ok(()));
+        }
+
+        assert_eq!(max_active.load(Ordering::SeqCst), 2);
+        assert_eq!(semaphore.available_permits(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn closed() {
+        let semaphore = Arc::new(Semaphore::new(1));
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(Error::Transient)
+        };
+
+        assert_eq!(
+            retry_future_limited(future, semaphore.clone()).await,
+            Err(LimitedError::Failed(Error::Transient))
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+
+        semaphore.close();
+
+        assert_eq!(
+            retry_future_limited(future, semaphore).await,
+            Err(LimitedError::Closed)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,108 @@
+//! Marker errors for operations that fail without any detail.
+use crate::Retryable;
+use log::Level;
+use std::fmt::Display;
+use std::time::Duration;
+use tryhard::RetryPolicy;
+
+/// An error with no detail that is always retried.
+///
+/// This is useful for operations that return `Result<T, ()>` (since
+/// `Retryable` can't be implemented for `()` outside of this crate): the
+/// error can be mapped with `map_err(|()| RetryAlways)`. It uses the same
+/// fixed configuration as boxed errors: up to five retries with the default
+/// backoff, starting with a 100 millisecond delay, logged at the warning
+/// level.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RetryAlways;
+
+/// An error with no detail that is never retried.
+///
+/// The operation is attempted once (and nothing is logged), as if it were
+/// called without a retry loop, which is useful where a `Retryable` error is required but retrying
+/// isn't appropriate.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct RetryNever;
+
+impl Display for RetryAlways {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Operation failed")
+    }
+}
+
+impl Display for RetryNever {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Operation failed")
+    }
+}
+
+impl std::error::Error for RetryAlways {}
+
+impl std::error::Error for RetryNever {}
+
+impl Retryable for RetryAlways {
+    fn max_retries() -> u32 {
+        5
+    }
+
+    fn default_initial_delay() -> Duration {
+        Duration::from_millis(100)
+    }
+
+    fn log_level() -> Option<Level> {
+        Some(Level::Warn)
+    }
+
+    fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+        None
+    }
+}
+
+impl Retryable for RetryNever {
+    fn max_retries() -> u32 {
+        0
+    }
+
+    fn default_initial_delay() -> Duration {
+        Duration::ZERO
+    }
+
+    fn log_level() -> Option<Level> {
+        None
+    }
+
+    fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+        Some(RetryPolicy::Break)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retry_future;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_always() {
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), ()>(()).map_err(|()| RetryAlways)
+        };
+
+        assert_eq!(retry_future(future).await, Err(RetryAlways));
+        assert_eq!(attempts.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_never() {
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(RetryNever)
+        };
+
+        assert_eq!(retry_future(future).await, Err(RetryNever));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,154 @@
+//! Retrying operations represented as values.
+use crate::{effective_max_retries, trace_start, RetryDriver, RetryStep, Retryable};
+use std::fmt::Debug;
+use std::future::Future;
+
+/// An operation that can be attempted any number of times.
+///
+/// This is a nameable alternative to the closures that `retry_future` takes,
+/// which is useful for storing a configured operation in a struct field.
+/// Implementations can use `async fn run(&mut self)`, and any `FnMut` closure
+/// that returns a future of a result is an operation.
+pub trait Operation {
+    type Output;
+    type Error;
+
+    /// Make a single attempt.
+    fn run(&mut self) -> impl Future<Output = Result<Self::Output, Self::Error>>;
+}
+
+impl<F, Fut, T, E> Operation for F
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    type Output = T;
+    type Error = E;
+
+    fn run(&mut self) -> impl Future<Output = Result<T, E>> {
+        self()
+    }
+}
+
+/// Execute an operation with retries where the error type is `Retryable`.
+///
+/// The retry configuration and logging are the same as for `retry_future`,
+/// but since each attempt can borrow the operation mutably, its state doesn't
+/// need to be shared between attempts through interior mutability.
+pub async fn retry_operation<O>(mut operation: O) -> Result<O::Output, O::Error>
+where
+    O: Operation,
+    O::Error: Retryable + Debug,
+{
+    let mut driver = RetryDriver::new();
+
+    if let Some(error) = driver.not_attempted() {
+        return Err(error);
+    }
+
+    trace_start::<O::Error>(effective_max_retries::<O::Error>());
+
+    loop {
+        match driver.next(operation.run().await) {
+            RetryStep::Done(result) => return result,
+            RetryStep::WaitThen(delay) => {
+                driver.wait(delay).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tryhard::RetryPolicy;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub enum Error {
+        Transient,
+        Fatal,
+    }
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            5
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(10)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            match self {
+                Self::Transient => None,
+                Self::Fatal => Some(RetryPolicy::Break),
+            }
+        }
+    }
+
+    struct Countdown<'a> {
+        remaining: u32,
+        attempts: &'a mut Vec<u32>,
+    }
+
+    impl Operation for Countdown<'_> {
+        type Output = usize;
+        type Error = Error;
+
+        async fn run(&mut self) -> Result<usize, Error> {
+            self.attempts.push(self.remaining);
+
+            if self.remaining == 0 {
+                Ok(self.attempts.len())
+            } else {
+                self.remaining -= 1;
+                Err(Error::Transient)
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn operation() {
+        let mut attempts = vec![];
+        let countdown = Countdown {
+            remaining: 3,
+            attempts: &mut attempts,
+        };
+
+        assert_eq!(retry_operation(countdown).await, Ok(4));
+        assert_eq!(attempts, vec![3, 2, 1, 0]);
+
+        let mut attempts = vec![];
+        let countdown = Countdown {
+            remaining: 10,
+            attempts: &mut attempts,
+        };
+
+        assert_eq!(retry_operation(countdown).await, Err(Error::Transient));
+        assert_eq!(attempts.len(), 6);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn closure() {
+        let mut attempts = 0;
+        let result = retry_operation(|| {
+            attempts += 1;
+            let error = if attempts < 3 {
+                Error::Transient
+            } else {
+                Error::Fatal
+            };
+
+            async move { Err::<(), _>(error) }
+        })
+        .await;
+
+        assert_eq!(result, Err(Error::Fatal));
+        assert_eq!(attempts, 3);
+    }
+}
@@ -0,0 +1,327 @@
+//! A single error type for the ways that a retry loop can give up.
+use crate::{retry_future_detailed, RetryFailure, Retryable, StopReason};
+use std::fmt::{Debug, Display};
+use std::future::Future;
+
+/// The terminal outcome of a retry loop that gave up, with the error that it
+/// reports.
+///
+/// The reported error can be recovered with `into_error`, and
+/// `impl_from_retry_error!` implements `From<RetryError<E>>` for specific
+/// error types (the orphan rules keep this crate from implementing it for
+/// every error type).
+#[derive(Debug, Eq, PartialEq)]
+pub enum RetryError<E> {
+    /// The maximum number of retries was reached.
+    Exhausted {
+        /// The reported error.
+        error: E,
+        /// The total number of attempts, including the first.
+        attempts: u32,
+    },
+    /// The error wasn't retried, because its policy was `Break` or because it
+    /// happened outside the error type's `retry_window`.
+    Broken {
+        /// The reported error.
+        error: E,
+    },
+    /// Retries were disabled by the kill switch (see `set_retries_enabled`).
+    Disabled {
+        /// The reported error.
+        error: E,
+    },
+    /// The operation wasn't attempted, because the error type's configuration
+    /// doesn't allow any attempts (see `Retryable::on_not_attempted`).
+    NotAttempted {
+        /// The error type's `on_not_attempted` error.
+        error: E,
+    },
+    /// A shared budget (such as a `RetrySession`'s) ran out of retries before
+    /// the error type's maximum was reached.
+    BudgetExhausted {
+        /// The reported error.
+        error: E,
+        /// The total number of attempts, including the first.
+        attempts: u32,
+    },
+    /// Waiting for the next delay would have exceeded the maximum elapsed
+    /// time.
+    Deadline {
+        /// The reported error.
+        error: E,
+    },
+    /// The loop was aborted by the caller (see `retry_future_abortable`).
+    Cancelled {
+        /// The error that aborted the loop.
+        error: E,
+    },
+}
+
+impl<E> RetryError<E> {
+    /// Describe an error that a loop stopped on for the given reason after
+    /// the given number of attempts.
+    pub(crate) fn stopped(error: E, reason: StopReason, attempts: u32) -> Self {
+        match reason {
+            StopReason::Exhausted => Self::Exhausted { error, attempts },
+            StopReason::Break | StopReason::OutsideWindow => Self::Broken { error },
+            StopReason::Disabled => Self::Disabled { error },
+            StopReason::NotAttempted => Self::NotAttempted { error },
+            StopReason::BudgetExhausted => Self::BudgetExhausted { error, attempts },
+            StopReason::Deadline => Self::Deadline { error },
+        }
+    }
+
+    /// Return the reported error.
+    pub fn error(&self) -> &E {
+        match self {
+            Self::Exhausted { error, .. }
+            | Self::Broken { error }
+            | Self::Disabled { error }
+            | Self::NotAttempted { error }
+            | Self::BudgetExhausted { error, .. }
+            | Self::Deadline { error }
+            | Self::Cancelled { error } => error,
+        }
+    }
+
+    /// Return the reported error, discarding the outcome.
+    pub fn into_error(self) -> E {
+        match self {
+            Self::Exhausted { error, .. }
+            | Self::Broken { error }
+            | Self::Disabled { error }
+            | Self::NotAttempted { error }
+            | Self::BudgetExhausted { error, .. }
+            | Self::Deadline { error }
+            | Self::Cancelled { error } => error,
+        }
+    }
+}
+
+impl<E> From<RetryFailure<E>> for RetryError<E> {
+    fn from(failure: RetryFailure<E>) -> Self {
+        Self::stopped(failure.error, failure.reason, failure.stats.attempts)
+    }
+}
+
+/// Implement `From<RetryError<E>>` for the given error types, so that a
+/// `RetryError` can be converted back into (or `?`-propagated as) the error
+/// that it reports.
+///
+/// These conversions can't be provided for every error type, since the orphan
+/// rules only allow them in the crate that defines the error type.
+///
+/// ```
+/// use retryable_error::{impl_from_retry_error, RetryError};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Error;
+///
+/// impl_from_retry_error!(Error);
+///
+/// let error = RetryError::Broken { error: Error };
+/// assert_eq!(Error::from(error), Error);
+/// ```
+#[macro_export]
+macro_rules! impl_from_retry_error {
+    ($($error:ty),+ $(,)?) => {
+        $(
+            impl ::core::convert::From<$crate::RetryError<$error>> for $error {
+                fn from(error: $crate::RetryError<$error>) -> Self {
+                    error.into_error()
+                }
+            }
+        )+
+    };
+}
+
+impl<E: Display> Display for RetryError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exhausted { error, attempts } => {
+                write!(
+                    f,
+                    "Retries exhausted after {} attempts: {}",
+                    attempts, error
+                )
+            }
+            Self::Broken { error } => write!(f, "Not retryable: {}", error),
+            Self::Disabled { error } => write!(f, "Retries disabled: {}", error),
+            Self::NotAttempted { error } => write!(f, "Not attempted: {}", error),
+            Self::BudgetExhausted { error, attempts } => {
+                write!(
+                    f,
+                    "Retry budget exhausted after {} attempts: {}",
+                    attempts, error
+                )
+            }
+            Self::Deadline { error } => write!(f, "Deadline exceeded: {}", error),
+            Self::Cancelled { error } => write!(f, "Retries cancelled: {}", error),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for RetryError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error())
+    }
+}
+
+/// Execute a future with retries, returning an error that describes how the
+/// loop gave up.
+///
+/// This gives callers a single type to match on for every terminal outcome,
+/// and the retry behavior (and logging) is the same as for
+/// `retry_future_detailed`. Use `retry_future` if only the error is needed.
+pub fn retry_future_result<F, Fut, T, E>(f: F) -> impl Future<Output = Result<T, RetryError<E>>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let future = retry_future_detailed(None, f);
+
+    async move { future.await.map_err(RetryError::from) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::SingleCallError;
+    use crate::RetryStats;
+    use std::time::Duration;
+    use tryhard::RetryPolicy;
+
+    #[derive(thiserror::Error, Debug, Eq, PartialEq)]
+    pub enum Error {
+        #[error("Transient")]
+        Transient,
+        #[error("Slow")]
+        Slow,
+        #[error("Fatal")]
+        Fatal,
+    }
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(10)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn max_elapsed() -> Option<Duration> {
+            Some(Duration::from_secs(1))
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            match self {
+                Self::Transient => None,
+                Self::Slow => Some(RetryPolicy::Delay(Duration::from_secs(2))),
+                Self::Fatal => Some(RetryPolicy::Break),
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn outcomes() {
+        let result = retry_future_result(|| async { Err::<(), _>(Error::Transient) }).await;
+        assert_eq!(
+            result,
+            Err(RetryError::Exhausted {
+                error: Error::Transient,
+                attempts: 4,
+            })
+        );
+
+        let result = retry_future_result(|| async { Err::<(), _>(Error::Fatal) }).await;
+        assert_eq!(
+            result,
+            Err(RetryError::Broken {
+                error: Error::Fatal
+            })
+        );
+
+        let result = retry_future_result(|| async { Err::<(), _>(Error::Slow) }).await;
+        assert_eq!(result, Err(RetryError::Deadline { error: Error::Slow }));
+
+        let result = retry_future_result(|| async { Ok::<_, Error>(1) }).await;
+        assert_eq!(result, Ok(1));
+    }
+
+    #[test]
+    fn from_failure() {
+        let failure = |reason| RetryFailure {
+            label: "fetch".to_string(),
+            error: Error::Transient,
+            reason,
+            stats: RetryStats {
+                attempts: 2,
+                total_delay: Duration::from_millis(10),
+                last_error: Some("Transient".to_string()),
+            },
+            recent_errors: vec![],
+        };
+
+        assert_eq!(
+            RetryError::from(failure(StopReason::OutsideWindow)),
+            RetryError::Broken {
+                error: Error::Transient
+            }
+        );
+
+        let error = RetryError::from(failure(StopReason::Disabled));
+        assert_eq!(error.to_string(), "Retries disabled: Transient");
+        assert_eq!(error.into_error(), Error::Transient);
+
+        let error = RetryError::from(failure(StopReason::NotAttempted));
+        assert_eq!(error.to_string(), "Not attempted: Transient");
+        assert_eq!(error.into_error(), Error::Transient);
+
+        let error = RetryError::from(failure(StopReason::BudgetExhausted));
+        assert_eq!(
+            error.to_string(),
+            "Retry budget exhausted after 2 attempts: Transient"
+        );
+        assert_eq!(error.error(), &Error::Transient);
+
+        let error = RetryError::from(failure(StopReason::Exhausted));
+        assert_eq!(
+            error.to_string(),
+            "Retries exhausted after 2 attempts: Transient"
+        );
+        assert_eq!(error.error(), &Error::Transient);
+    }
+
+    crate::impl_from_retry_error!(Error);
+
+    #[test]
+    fn from_retry_error() {
+        let error = RetryError::Cancelled {
+            error: Error::Fatal,
+        };
+        assert_eq!(error.to_string(), "Retries cancelled: Fatal");
+        assert_eq!(Error::from(error), Error::Fatal);
+
+        fn propagate() -> Result<(), Error> {
+            Err(RetryError::Deadline { error: Error::Slow })?
+        }
+        assert_eq!(propagate(), Err(Error::Slow));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn not_attempted() {
+        let result = retry_future_result(|| async { Err::<(), _>(SingleCallError::Failed) }).await;
+        assert_eq!(
+            result,
+            Err(RetryError::NotAttempted {
+                error: SingleCallError::NotAttempted
+            })
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Retries for operations that report partial progress on failure.
+use crate::forward::{forward_error_options, forward_static_options};
+use crate::{retry_with_defaults, Retryable};
+use std::fmt::Debug;
+use std::future::Future;
+
+/// Execute a future with retries where errors may carry partial progress.
+///
+/// The operation's error type is `(E, Option<P>)`, where `E` is `Retryable`
+/// and `P` describes whatever progress the failed attempt made (for example
+/// the number of chunks uploaded). Retry decisions and log messages only use
+/// `E`, and the partial progress of retried attempts is discarded. When the
+/// loop gives up, the final error is returned together with the partial
+/// progress of the last attempt, so that the caller can resume instead of
+/// restarting.
+pub fn retry_future_partial<F, Fut, T, E, P>(
+    mut f: F,
+) -> impl Future<Output = Result<T, (E, Option<P>)>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, (E, Option<P>)>>,
+    E: Retryable + Debug,
+{
+    let future = retry_with_defaults(move || {
+        let future = f();
+
+        async move {
+            future
+                .await
+                .map_err(|(error, progress)| Partial { error, progress })
+        }
+    });
+
+    async move {
+        future
+            .await
+            .map_err(|partial| (partial.error, partial.progress))
+    }
+}
+
+struct Partial<E, P> {
+    error: E,
+    progress: Option<P>,
+}
+
+impl<E: Debug, P> Debug for Partial<E, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<E: Retryable, P> Retryable for Partial<E, P> {
+    forward_static_options!(E);
+
+    fn on_not_attempted() -> Option<Self> {
+        E::on_not_attempted().map(|error| Self {
+            error,
+            progress: None,
+        })
+    }
+
+    forward_error_options!(match self {
+        Self { error, .. } => error,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+    use tryhard::RetryPolicy;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct Error(bool);
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(1)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            if self.0 {
+                None
+            } else {
+                Some(RetryPolicy::Break)
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn exhausted_with_progress() {
+        let attempts = AtomicU32::new(0);
+
+        let future = || async {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            let result: Result<(), (Error, Option<u32>)> = Err((Error(true), Some(attempt)));
+
+            result
+        };
+
+        assert_eq!(
+            retry_future_partial(future).await,
+            Err((Error(true), Some(3)))
+        );
+    }
+
+    #[tokio::test]
+    async fn broken_without_progress() {
+        let attempts = AtomicU32::new(0);
+
+        let future = || async {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            let result: Result<(), (Error, Option<u32>)> = if attempt < 1 {
+                Err((Error(true), Some(attempt)))
+            } else {
+                Err((Error(false), None))
+            };
+
+            result
+        };
+
+        assert_eq!(
+            retry_future_partial(future).await,
+            Err((Error(false), None))
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+}
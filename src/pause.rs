@@ -0,0 +1,168 @@
+//! Pausing retry loops without aborting them.
+use crate::{effective_max_retries, trace_start, RetryDriver, RetryStep, Retryable};
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// A shared switch for pausing retry loops (for example during a maintenance
+/// window).
+///
+/// Clones share the same state, which starts unpaused.
+#[derive(Clone, Debug, Default)]
+pub struct PauseSwitch {
+    state: Arc<PauseState>,
+}
+
+#[derive(Debug, Default)]
+struct PauseState {
+    paused: AtomicBool,
+    resumed: Notify,
+}
+
+impl PauseSwitch {
+    /// Create an unpaused switch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pause the loops that use this switch (at their next attempt).
+    pub fn pause(&self) {
+        self.state.paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Resume the loops that use this switch, waking any that are paused.
+    pub fn resume(&self) {
+        self.state.paused.store(false, Ordering::SeqCst);
+        self.state.resumed.notify_waiters();
+    }
+
+    /// Indicate whether the switch is currently paused.
+    pub fn is_paused(&self) -> bool {
+        self.state.paused.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the switch isn't paused.
+    async fn wait_until_resumed(&self) {
+        loop {
+            let mut resumed = pin!(self.state.resumed.notified());
+            // Register for the notification before checking the flag, so that
+            // a resume in between can't be missed.
+            resumed.as_mut().enable();
+
+            if !self.is_paused() {
+                return;
+            }
+
+            resumed.await;
+        }
+    }
+}
+
+/// Execute a future with retries, holding before each attempt while the switch
+/// is paused.
+///
+/// Unlike aborting, pausing doesn't end the loop: once the switch is resumed,
+/// the loop continues where it left off. Time spent paused doesn't count
+/// toward the maximum elapsed time, and waiting doesn't use up any attempts.
+pub async fn retry_future_pausable<F, Fut, T, E>(mut f: F, pause: &PauseSwitch) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let mut driver = RetryDriver::new();
+
+    if let Some(error) = driver.not_attempted() {
+        return Err(error);
+    }
+
+    trace_start::<E>(effective_max_retries::<E>());
+
+    loop {
+        if pause.is_paused() {
+            let paused = Instant::now();
+            pause.wait_until_resumed().await;
+            driver.exclude_from_elapsed(paused.elapsed());
+        }
+
+        match driver.next(f().await) {
+            RetryStep::Done(result) => return result,
+            RetryStep::WaitThen(delay) => {
+                driver.wait(delay).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+    use std::time::Duration;
+    use tryhard::RetryPolicy;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct Error;
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn max_elapsed() -> Option<Duration> {
+            Some(Duration::from_secs(1))
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pause_mid_loop() {
+        let pause = PauseSwitch::new();
+        let attempts = Arc::new(AtomicU32::new(0));
+        let started = Instant::now();
+
+        let resumer = {
+            let pause = pause.clone();
+            let attempts = attempts.clone();
+
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                assert_eq!(attempts.load(Ordering::SeqCst), 2);
+                pause.resume();
+            })
+        };
+
+        let future = || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) == 1 {
+                pause.pause();
+            }
+
+            Err::<(), _>(Error)
+        };
+
+        assert_eq!(retry_future_pausable(future, &pause).await, Err(Error));
+        resumer.await.unwrap();
+
+        // The pause doesn't count toward the one second limit.
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+        assert_eq!(
+            started.elapsed(),
+            Duration::from_secs(60) + Duration::from_millis(400)
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! A thin layer over `tryhard`'s retry policies.
+//!
+//! Every retry decision is interpreted here, so that if `tryhard` adds new
+//! kinds of policies, this is the only module that needs to handle them.
+use std::time::Duration;
+use tryhard::RetryPolicy;
+
+/// A normalized retry decision.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Decision {
+    /// Retry after the delay.
+    Retry(Duration),
+    /// Stop retrying.
+    Stop,
+}
+
+impl Decision {
+    /// Return the delay before the next attempt, if there is one.
+    pub(crate) fn delay(self) -> Option<Duration> {
+        match self {
+            Self::Retry(delay) => Some(delay),
+            Self::Stop => None,
+        }
+    }
+}
+
+/// Any policy that isn't recognized stops the retry loop, since retrying for
+/// reasons we don't understand is less safe than giving up.
+impl From<RetryPolicy> for Decision {
+    #[allow(unreachable_patterns)]
+    fn from(policy: RetryPolicy) -> Self {
+        match policy {
+            RetryPolicy::Delay(delay) => Self::Retry(delay),
+            RetryPolicy::Break => Self::Stop,
+            _ => Self::Stop,
+        }
+    }
+}
+
+impl From<Decision> for RetryPolicy {
+    fn from(decision: Decision) -> Self {
+        match decision {
+            Decision::Retry(delay) => Self::Delay(delay),
+            Decision::Stop => Self::Break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapping() {
+        let delay = Duration::from_millis(100);
+
+        assert_eq!(
+            Decision::from(RetryPolicy::Delay(delay)),
+            Decision::Retry(delay)
+        );
+        assert_eq!(Decision::from(RetryPolicy::Break), Decision::Stop);
+        assert_eq!(
+            RetryPolicy::from(Decision::Retry(delay)),
+            RetryPolicy::Delay(delay)
+        );
+        assert_eq!(RetryPolicy::from(Decision::Stop), RetryPolicy::Break);
+        assert_eq!(Decision::Retry(delay).delay(), Some(delay));
+        assert_eq!(Decision::Stop.delay(), None);
+    }
+}
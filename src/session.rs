@@ -0,0 +1,341 @@
+//! Sharing a retry budget across the steps of a workflow.
+use crate::{
+    effective_max_retries, trace_start, RetryDriver, RetryError, RetryStep, Retryable, StopReason,
+};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// A budget of time and retries shared by several retry loops.
+///
+/// This is intended for multi-step workflows (for example authenticating,
+/// then fetching, then committing), where each step should be retried, but a
+/// slow early step should eat into the allowance of the later ones. The
+/// elapsed time is measured from when the session is created, and each step
+/// is also limited by its own error type's configuration.
+///
+/// Every step makes at least one attempt, even if the budget has already been
+/// used up (unless its error type's `on_not_attempted` applies).
+///
+/// A session can also have budgets for kinds of errors (identified by their
+/// `session_budget_key`). Each retry after an error with a key uses up its
+/// budget, as well as the session's, and once it's used up, errors with that
+/// key are no longer retried in any step.
+#[derive(Debug)]
+pub struct RetrySession {
+    started: Instant,
+    max_elapsed: Option<Duration>,
+    max_retries: Option<u32>,
+    retries: u32,
+    key_budgets: HashMap<&'static str, u32>,
+    key_retries: HashMap<&'static str, u32>,
+}
+
+impl RetrySession {
+    /// Create a session with no limits of its own.
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            max_elapsed: None,
+            max_retries: None,
+            retries: 0,
+            key_budgets: HashMap::new(),
+            key_retries: HashMap::new(),
+        }
+    }
+
+    /// Limit the total time available to all steps.
+    pub fn max_elapsed(self, max_elapsed: Duration) -> Self {
+        Self {
+            max_elapsed: Some(max_elapsed),
+            ..self
+        }
+    }
+
+    /// Limit the total number of retries available to all steps.
+    pub fn max_retries(self, max_retries: u32) -> Self {
+        Self {
+            max_retries: Some(max_retries),
+            ..self
+        }
+    }
+
+    /// Limit the total number of retries available to all steps after errors
+    /// with the given session budget key.
+    pub fn key_budget(mut self, key: &'static str, max_retries: u32) -> Self {
+        self.key_budgets.insert(key, max_retries);
+
+        self
+    }
+
+    /// Return the number of retries that have been made by all steps so far.
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// Return the number of retries that have been made by all steps so far
+    /// after errors with the given session budget key.
+    pub fn key_retries(&self, key: &str) -> u32 {
+        self.key_retries.get(key).copied().unwrap_or(0)
+    }
+
+    /// Indicate whether the budget for the given key has been used up.
+    fn key_budget_exhausted(&self, key: &str) -> bool {
+        self.key_budgets
+            .get(key)
+            .is_some_and(|max_retries| self.key_retries(key) >= *max_retries)
+    }
+
+    /// Execute a step with retries, using and updating the shared budget.
+    pub async fn run<F, Fut, T, E>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Retryable + Debug,
+    {
+        self.run_result(f).await.map_err(RetryError::into_error)
+    }
+
+    /// Execute a step with retries, returning an error that describes how the
+    /// step gave up.
+    ///
+    /// This is the same as `run`, except that a step that stops because the
+    /// session's budgets are used up fails with `RetryError::BudgetExhausted`.
+    pub async fn run_result<F, Fut, T, E>(&mut self, mut f: F) -> Result<T, RetryError<E>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Retryable + Debug,
+    {
+        let mut driver = RetryDriver::new();
+
+        if let Some(max_elapsed) = self.max_elapsed {
+            driver.limit_elapsed(self.started, max_elapsed);
+        }
+
+        if let Some(max_retries) = self.max_retries {
+            driver.limit_retries(max_retries.saturating_sub(self.retries));
+        }
+
+        if let Some(error) = driver.not_attempted() {
+            return Err(RetryError::NotAttempted { error });
+        }
+
+        trace_start::<E>(effective_max_retries::<E>());
+
+        let mut attempts = 0;
+
+        loop {
+            attempts += 1;
+            let result = f().await;
+            let key = result.as_ref().err().and_then(E::session_budget_key);
+            let step = if key.is_some_and(|key| self.key_budget_exhausted(key)) {
+                driver.next_without_retry(result, StopReason::BudgetExhausted)
+            } else {
+                driver.next(result)
+            };
+
+            match step {
+                RetryStep::Done(result) => {
+                    return result.map_err(|error| {
+                        let reason = driver
+                            .stop_reason()
+                            .expect("the driver records the reason for stopping");
+
+                        RetryError::stopped(error, reason, attempts)
+                    });
+                }
+                RetryStep::WaitThen(delay) => {
+                    self.retries += 1;
+
+                    if let Some(key) = key {
+                        *self.key_retries.entry(key).or_insert(0) += 1;
+                    }
+
+                    driver.wait(delay).await;
+                }
+            }
+        }
+    }
+}
+
+impl Default for RetrySession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestError;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tryhard::RetryPolicy;
+
+    type Error = TestError<100>;
+
+    fn failing_until(
+        attempts: &AtomicU32,
+        successes_after: u32,
+    ) -> impl FnMut() -> std::future::Ready<Result<(), Error>> + '_ {
+        move || {
+            if attempts.fetch_add(1, Ordering::SeqCst) < successes_after {
+                std::future::ready(Err(Error::Transient))
+            } else {
+                std::future::ready(Ok(()))
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shared_elapsed() {
+        let mut session = RetrySession::new().max_elapsed(Duration::from_secs(1));
+        let started = Instant::now();
+
+        // The first step takes 700 milliseconds to succeed.
+        let attempts = AtomicU32::new(0);
+        assert_eq!(session.run(failing_until(&attempts, 3)).await, Ok(()));
+        assert_eq!(started.elapsed(), Duration::from_millis(700));
+
+        // The second step would need another 700 milliseconds to use all of
+        // its retries, so it stops one retry early.
+        let attempts = AtomicU32::new(0);
+        assert_eq!(
+            session.run(failing_until(&attempts, u32::MAX)).await,
+            Err(Error::Transient)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(started.elapsed(), Duration::from_millis(1000));
+        assert_eq!(session.retries(), 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shared_retries() {
+        let mut session = RetrySession::new().max_retries(4);
+
+        let attempts = AtomicU32::new(0);
+        assert_eq!(session.run(failing_until(&attempts, 3)).await, Ok(()));
+        assert_eq!(session.retries(), 3);
+
+        let attempts = AtomicU32::new(0);
+        assert_eq!(
+            session.run(failing_until(&attempts, u32::MAX)).await,
+            Err(Error::Transient)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        // Later steps still make one attempt.
+        let attempts = AtomicU32::new(0);
+        assert_eq!(
+            session.run(failing_until(&attempts, u32::MAX)).await,
+            Err(Error::Transient)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(session.retries(), 4);
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub enum KeyedError {
+        Unauthorized,
+        Unavailable,
+    }
+
+    impl Retryable for KeyedError {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(100)
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            None
+        }
+
+        fn session_budget_key(&self) -> Option<&'static str> {
+            match self {
+                Self::Unauthorized => Some("auth"),
+                Self::Unavailable => None,
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn key_budget() {
+        let mut session = RetrySession::new().key_budget("auth", 3);
+
+        // The first step uses two of the retries for authentication failures.
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(KeyedError::Unauthorized),
+                _ => Ok(()),
+            }
+        };
+        assert_eq!(session.run(future).await, Ok(()));
+        assert_eq!(session.key_retries("auth"), 2);
+
+        // The second step only has one left, but other errors are still
+        // retried as usual.
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err::<(), _>(KeyedError::Unavailable),
+                _ => Err(KeyedError::Unauthorized),
+            }
+        };
+        assert_eq!(session.run(future).await, Err(KeyedError::Unauthorized));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+        assert_eq!(session.key_retries("auth"), 3);
+        assert_eq!(session.retries(), 5);
+
+        // Once the budget is used up, the error isn't retried at all.
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(KeyedError::Unauthorized)
+        };
+        assert_eq!(session.run(future).await, Err(KeyedError::Unauthorized));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn budget_exhausted() {
+        let mut session = RetrySession::new().max_retries(5).key_budget("auth", 0);
+
+        // The session's budget is larger than the error type's maximum.
+        let attempts = AtomicU32::new(0);
+        assert_eq!(
+            session.run_result(failing_until(&attempts, u32::MAX)).await,
+            Err(RetryError::Exhausted {
+                error: Error::Transient,
+                attempts: 4
+            })
+        );
+
+        let attempts = AtomicU32::new(0);
+        assert_eq!(
+            session.run_result(failing_until(&attempts, u32::MAX)).await,
+            Err(RetryError::BudgetExhausted {
+                error: Error::Transient,
+                attempts: 3
+            })
+        );
+
+        let future = || async { Err::<(), _>(KeyedError::Unauthorized) };
+        assert_eq!(
+            session.run_result(future).await,
+            Err(RetryError::BudgetExhausted {
+                error: KeyedError::Unauthorized,
+                attempts: 1
+            })
+        );
+    }
+}
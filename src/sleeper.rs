@@ -0,0 +1,153 @@
+//! Waiting between attempts without the Tokio timer.
+//!
+//! The crate always depends on Tokio, since the `Retryable` policies are
+//! `tryhard`'s and `tryhard` requires Tokio, so there is no build without it
+//! (for example for WASM targets that Tokio doesn't support). A sleeper only
+//! replaces the timer that the delays are waited on, so that the loop can be
+//! run by another executor.
+use crate::{effective_max_retries, trace_start, RetryDriver, RetryStep, Retryable};
+use std::fmt::Debug;
+use std::future::Future;
+use std::time::Duration;
+
+/// A way of waiting for the delay between attempts.
+///
+/// Any function that takes a delay and returns a future is a sleeper, so an
+/// executor's own timer can be plugged in directly (for example
+/// `|delay| async move { async_io::Timer::after(delay).await; }`).
+pub trait Sleeper {
+    type Sleep: Future<Output = ()>;
+
+    /// Return a future that completes after the delay.
+    fn sleep(&self, delay: Duration) -> Self::Sleep;
+}
+
+impl<F, Fut> Sleeper for F
+where
+    F: Fn(Duration) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    type Sleep = Fut;
+
+    fn sleep(&self, delay: Duration) -> Fut {
+        self(delay)
+    }
+}
+
+/// A sleeper that uses the Tokio timer, as the other retry functions do.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioSleeper;
+
+impl Sleeper for TokioSleeper {
+    type Sleep = tokio::time::Sleep;
+
+    fn sleep(&self, delay: Duration) -> tokio::time::Sleep {
+        tokio::time::sleep(delay)
+    }
+}
+
+/// Execute a future with retries, waiting between attempts with the given
+/// sleeper.
+///
+/// The retry configuration and logging are the same as for `retry_future`,
+/// but the loop doesn't need the Tokio timer, so it can be run by any
+/// executor with a sleeper for that executor. The functions that are run by
+/// `tryhard` (such as `retry_future` itself) always use the Tokio timer, and
+/// need a Tokio runtime with the timer enabled.
+///
+/// The sleeper is also used for zero delays, and the cooperative budget and
+/// the yield before a zero-delay retry (see `Retryable::cooperate` and
+/// `Retryable::yield_between_attempts`) use Tokio's task helpers, which simply
+/// wake the task again when there's no Tokio runtime.
+pub async fn retry_future_with_sleeper<F, Fut, T, E, S>(mut f: F, sleeper: &S) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+    S: Sleeper,
+{
+    let mut driver = RetryDriver::new();
+
+    if let Some(error) = driver.not_attempted() {
+        return Err(error);
+    }
+
+    trace_start::<E>(effective_max_retries::<E>());
+
+    loop {
+        match driver.next(f().await) {
+            RetryStep::Done(result) => return result,
+            RetryStep::WaitThen(delay) => {
+                sleeper.sleep(delay).await;
+                driver.before_retry(delay).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestError;
+    use std::cell::RefCell;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    type Error = TestError<10>;
+
+    /// Run a future to completion by polling it in a loop, without a runtime.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn without_runtime() {
+        let delays = RefCell::new(vec![]);
+        let sleeper = |delay: Duration| {
+            delays.borrow_mut().push(delay.as_millis());
+            std::future::ready(())
+        };
+
+        let mut attempts = 0;
+        let result = block_on(retry_future_with_sleeper(
+            || {
+                attempts += 1;
+                std::future::ready(if attempts < 3 {
+                    Err(Error::Transient)
+                } else {
+                    Ok(attempts)
+                })
+            },
+            &sleeper,
+        ));
+
+        assert_eq!(result, Ok(3));
+        assert_eq!(*delays.borrow(), vec![10, 20]);
+
+        let result = block_on(retry_future_with_sleeper(
+            || std::future::ready(Err::<(), _>(Error::Transient)),
+            &sleeper,
+        ));
+
+        assert_eq!(result, Err(Error::Transient));
+        assert_eq!(*delays.borrow(), vec![10, 20, 10, 20, 40]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tokio_sleeper() {
+        let started = tokio::time::Instant::now();
+        let result =
+            retry_future_with_sleeper(|| async { Err::<(), _>(Error::Transient) }, &TokioSleeper)
+                .await;
+
+        assert_eq!(result, Err(Error::Transient));
+        assert_eq!(started.elapsed(), Duration::from_millis(70));
+    }
+}
@@ -0,0 +1,282 @@
+//! Declarative retry configurations that can be loaded from files.
+use crate::{
+    at_least_min_retry_delay, retry_with_options, BackoffSettings, ErrorBackoff, LogOnRetry,
+};
+use log::Level;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::future::Future;
+use std::time::Duration;
+use tryhard::{backoff_strategies::BackoffStrategy, RetryFutureConfig, RetryPolicy};
+
+/// A retry configuration provided as data instead of by a `Retryable` impl.
+///
+/// Delays are (de)serialized as integer numbers of milliseconds, and all
+/// fields except `max_retries` and `initial_delay_ms` are optional. The
+/// `jitter` field is only available with the `jitter` feature (and is
+/// ignored otherwise).
+///
+/// ```json
+/// {
+///   "max_retries": 5,
+///   "initial_delay_ms": 100,
+///   "max_delay_ms": 2000,
+///   "multiplier": 1.5,
+///   "log_level": "WARN",
+///   "jitter": "full"
+/// }
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RetryConfigSpec {
+    /// The maximum number of retries after the first attempt.
+    pub max_retries: u32,
+    /// The initial default delay.
+    #[serde(rename = "initial_delay_ms", with = "millis")]
+    pub initial_delay: Duration,
+    /// The maximum default delay.
+    #[serde(
+        rename = "max_delay_ms",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "optional_millis"
+    )]
+    pub max_delay: Option<Duration>,
+    /// The factor that each default delay is multiplied by (`2.0` by
+    /// default).
+    #[serde(default = "default_multiplier")]
+    pub multiplier: f64,
+    /// The log level (an empty value indicates that no logging will be done).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<Level>,
+    /// The jitter strategy for default delays.
+    #[cfg(feature = "jitter")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jitter: Option<crate::Jitter>,
+}
+
+fn default_multiplier() -> f64 {
+    2.0
+}
+
+impl RetryConfigSpec {
+    /// Build a retry configuration from this specification.
+    ///
+    /// The classification function plays the role of `custom_retry_policy`,
+    /// and an empty value represents the default.
+    pub fn retry_config<E, C>(
+        &self,
+        classify: C,
+    ) -> RetryFutureConfig<impl for<'a> BackoffStrategy<'a, E, Output = RetryPolicy>, LogOnRetry>
+    where
+        E: Debug,
+        C: FnMut(&E) -> Option<RetryPolicy>,
+    {
+        let backoff = SpecBackoff {
+            classify,
+            backoff: ErrorBackoff::from_settings(BackoffSettings {
+                initial_delay: self.initial_delay,
+                max_retries: self.max_retries,
+                max_elapsed: None,
+                multiplier: self.multiplier,
+                next_delay: None,
+                min_delay: None,
+                default_is_break: false,
+                quantum: None,
+                retry_window: None,
+                fit_to_max_elapsed: false,
+                #[cfg(feature = "jitter")]
+                jitter: self.jitter,
+                #[cfg(feature = "jitter")]
+                jitter_seed: None,
+            }),
+            max_delay: self.max_delay,
+        };
+
+        RetryFutureConfig::new(self.max_retries)
+            .on_retry(LogOnRetry::with_level(self.log_level))
+            .custom_backoff(backoff)
+    }
+}
+
+/// Execute a future with retries using a declarative configuration.
+///
+/// The error type doesn't need to be `Retryable`, since the classification
+/// function provides the policy for each error.
+pub fn retry_future_with_spec<F, Fut, T, E, C>(
+    f: F,
+    spec: &RetryConfigSpec,
+    classify: C,
+) -> impl Future<Output = Result<T, E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Debug,
+    C: FnMut(&E) -> Option<RetryPolicy>,
+{
+    let max_retries = spec.max_retries;
+    let spec = spec.clone();
+
+    retry_with_options(f, move || spec.retry_config(classify), max_retries)
+}
+
+struct SpecBackoff<E, C> {
+    classify: C,
+    backoff: ErrorBackoff<E>,
+    max_delay: Option<Duration>,
+}
+
+impl<'a, E, C> BackoffStrategy<'a, E> for SpecBackoff<E, C>
+where
+    C: FnMut(&E) -> Option<RetryPolicy>,
+{
+    type Output = RetryPolicy;
+
+    fn delay(&mut self, attempt: u32, error: &'a E) -> RetryPolicy {
+        let custom = (self.classify)(error);
+
+        at_least_min_retry_delay(
+            self.backoff
+                .decide(attempt, custom, self.max_delay)
+                .map_or(RetryPolicy::Break, RetryPolicy::Delay),
+        )
+    }
+}
+
+/// (De)serialize a duration as an integer number of milliseconds.
+pub(crate) mod millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(value.as_millis().try_into().unwrap_or(u64::MAX))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+mod optional_millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => super::millis::serialize(value, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<u64>::deserialize(deserializer).map(|value| value.map(Duration::from_millis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::time::Instant;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub enum Error {
+        Transient,
+        Fatal,
+    }
+
+    fn spec() -> RetryConfigSpec {
+        RetryConfigSpec {
+            max_retries: 5,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Some(Duration::from_millis(300)),
+            multiplier: 2.0,
+            log_level: Some(Level::Warn),
+            #[cfg(feature = "jitter")]
+            jitter: None,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let spec = spec();
+        let json = serde_json::to_string(&spec).unwrap();
+
+        assert_eq!(
+            json,
+            r#"{"max_retries":5,"initial_delay_ms":100,"max_delay_ms":300,"multiplier":2.0,"log_level":"WARN"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<RetryConfigSpec>(&json).unwrap(),
+            spec
+        );
+    }
+
+    #[test]
+    fn defaults() {
+        let spec: RetryConfigSpec =
+            serde_json::from_str(r#"{"max_retries":3,"initial_delay_ms":250}"#).unwrap();
+
+        assert_eq!(spec.max_retries, 3);
+        assert_eq!(spec.initial_delay, Duration::from_millis(250));
+        assert_eq!(spec.max_delay, None);
+        assert_eq!(spec.multiplier, 2.0);
+        assert_eq!(spec.log_level, None);
+        assert_eq!(
+            serde_json::from_str::<RetryConfigSpec>(&serde_json::to_string(&spec).unwrap())
+                .unwrap(),
+            spec
+        );
+    }
+
+    #[cfg(feature = "jitter")]
+    #[test]
+    fn jitter_round_trip() {
+        let spec = RetryConfigSpec {
+            jitter: Some(crate::Jitter::Absolute(Duration::from_millis(50))),
+            ..spec()
+        };
+        let json = serde_json::to_string(&spec).unwrap();
+
+        assert!(json.ends_with(r#""jitter":{"absolute":50}}"#));
+        assert_eq!(
+            serde_json::from_str::<RetryConfigSpec>(&json).unwrap(),
+            spec
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn with_spec() {
+        let attempts = AtomicU32::new(0);
+        let started = Instant::now();
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(Error::Transient)
+        };
+
+        let classify = |error: &Error| match error {
+            Error::Transient => None,
+            Error::Fatal => Some(RetryPolicy::Break),
+        };
+
+        assert_eq!(
+            retry_future_with_spec(future, &spec(), classify).await,
+            Err(Error::Transient)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 6);
+        assert_eq!(
+            started.elapsed(),
+            Duration::from_millis(100 + 200 + 300 * 3)
+        );
+
+        let future = || async { Err::<(), _>(Error::Fatal) };
+        assert_eq!(
+            retry_future_with_spec(future, &spec(), classify).await,
+            Err(Error::Fatal)
+        );
+    }
+}
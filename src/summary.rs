@@ -0,0 +1,154 @@
+//! Summarizing the delays used by a retry loop as a `tracing` event.
+use std::time::Duration;
+
+/// The maximum number of delays that are included in a summary event.
+const MAX_DELAYS: usize = 32;
+
+/// The delays used by a retry loop, which are emitted as a single `tracing`
+/// event when the loop's retry handler is dropped (if there were any
+/// retries).
+///
+/// The event lists the first 32 delays, while the number of retries and the
+/// total delay cover all of them.
+#[derive(Default)]
+pub(crate) struct DelaySchedule {
+    error_type: Option<&'static str>,
+    operation: Option<&'static str>,
+    delays: Vec<Duration>,
+    retries: u32,
+    total: Duration,
+}
+
+impl DelaySchedule {
+    pub(crate) fn for_type<E: ?Sized>() -> Self {
+        let mut schedule = Self::default();
+        schedule.error_type = Some(std::any::type_name::<E>());
+
+        schedule
+    }
+
+    pub(crate) fn named(mut self, operation: &'static str) -> Self {
+        self.operation = Some(operation);
+
+        self
+    }
+
+    pub(crate) fn record(&mut self, delay: Duration) {
+        if self.delays.len() < MAX_DELAYS {
+            self.delays.push(delay);
+        }
+
+        self.retries = self.retries.saturating_add(1);
+        self.total = self.total.saturating_add(delay);
+    }
+}
+
+impl Drop for DelaySchedule {
+    fn drop(&mut self) {
+        if self.retries > 0 {
+            tracing::debug!(
+                operation = self.operation,
+                error_type = self.error_type,
+                retries = self.retries,
+                total = ?self.total,
+                delays = ?self.delays,
+                "Retry summary"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::retry_future;
+    use crate::test_support::TestError;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::{span, Event, Metadata};
+
+    type Error = TestError<100>;
+
+    type Fields = Vec<(String, String)>;
+
+    /// A subscriber that records the fields of every event.
+    #[derive(Clone, Default)]
+    struct Capture {
+        events: Arc<Mutex<Vec<Fields>>>,
+    }
+
+    #[derive(Default)]
+    struct Visitor(Fields);
+
+    impl Visit for Visitor {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0
+                .push((field.name().to_string(), format!("{:?}", value)));
+        }
+    }
+
+    impl tracing::Subscriber for Capture {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _span: &span::Attributes<'_>) -> span::Id {
+            span::Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Visitor::default();
+            event.record(&mut fields);
+            self.events.lock().unwrap().push(fields.0);
+        }
+
+        fn enter(&self, _span: &span::Id) {}
+
+        fn exit(&self, _span: &span::Id) {}
+    }
+
+    #[test]
+    fn summary_event() {
+        let capture = Capture::default();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .start_paused(true)
+            .build()
+            .unwrap();
+
+        tracing::subscriber::with_default(capture.clone(), || {
+            runtime.block_on(async {
+                assert_eq!(retry_future(|| async { Ok::<_, Error>(()) }).await, Ok(()));
+                assert_eq!(
+                    retry_future(|| async { Err::<(), _>(Error::Transient) }).await,
+                    Err(Error::Transient)
+                );
+            })
+        });
+
+        let events = capture.events.lock().unwrap();
+        let summaries = events
+            .iter()
+            .filter(|fields| fields.contains(&("message".to_string(), "Retry summary".to_string())))
+            .collect::<Vec<_>>();
+
+        // Only the loop that retried is summarized.
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(
+            summaries[0][1..],
+            [
+                (
+                    "error_type".to_string(),
+                    format!("{:?}", std::any::type_name::<Error>())
+                ),
+                ("retries".to_string(), "3".to_string()),
+                ("total".to_string(), "700ms".to_string()),
+                ("delays".to_string(), "[100ms, 200ms, 400ms]".to_string()),
+            ]
+        );
+    }
+}
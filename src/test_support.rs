@@ -0,0 +1,63 @@
+//! Error types shared by the tests.
+use crate::SimpleRetryable;
+use tryhard::RetryPolicy;
+
+/// An error with up to three retries, starting with the given delay in
+/// milliseconds, where fatal errors aren't retried.
+///
+/// Tests usually name the delay they need with an alias (for example
+/// `type Error = TestError<100>;`).
+#[derive(Debug, Eq, PartialEq)]
+pub enum TestError<const INITIAL_DELAY_MS: u64> {
+    Transient,
+    Fatal,
+}
+
+impl<const INITIAL_DELAY_MS: u64> SimpleRetryable for TestError<INITIAL_DELAY_MS> {
+    const MAX_RETRIES: u32 = 3;
+    const INITIAL_DELAY_MS: u64 = INITIAL_DELAY_MS;
+
+    fn retry_policy(&self) -> Option<RetryPolicy> {
+        match self {
+            Self::Transient => None,
+            Self::Fatal => Some(RetryPolicy::Break),
+        }
+    }
+}
+
+/// An error type that only allows a single call, which can't be made when
+/// retries aren't allowed, so that its `on_not_attempted` error is returned
+/// without calling the operation at all.
+#[derive(thiserror::Error, Debug, Eq, PartialEq)]
+pub enum SingleCallError {
+    #[error("Failed")]
+    Failed,
+    #[error("Not attempted")]
+    NotAttempted,
+}
+
+impl crate::Retryable for SingleCallError {
+    fn max_retries() -> u32 {
+        0
+    }
+
+    fn count_first_attempt() -> bool {
+        true
+    }
+
+    fn on_not_attempted() -> Option<Self> {
+        Some(Self::NotAttempted)
+    }
+
+    fn default_initial_delay() -> std::time::Duration {
+        std::time::Duration::from_millis(10)
+    }
+
+    fn log_level() -> Option<log::Level> {
+        None
+    }
+
+    fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+        None
+    }
+}
@@ -0,0 +1,224 @@
+//! Timing out individual attempts.
+use crate::forward::{forward_error_options, forward_static_options};
+use crate::{
+    retry_future_with_backoff, retry_with_defaults, scale_delay, BackoffSettings, ErrorBackoff,
+    Retryable,
+};
+use std::fmt::{Debug, Display};
+use std::future::Future;
+use std::time::Duration;
+
+/// The error returned by `retry_future_with_attempt_timeout`.
+#[derive(Debug, Eq, PartialEq)]
+pub enum AttemptTimeoutError<E> {
+    /// The attempt didn't complete before the timeout.
+    TimedOut,
+    /// The attempt failed with the given error.
+    Failed(E),
+}
+
+impl<E: Display> Display for AttemptTimeoutError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TimedOut => write!(f, "Attempt timed out"),
+            Self::Failed(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for AttemptTimeoutError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::TimedOut => None,
+            Self::Failed(error) => Some(error),
+        }
+    }
+}
+
+/// Errors use the configuration of the underlying error type. Timeouts aren't
+/// passed to the underlying type's `custom_retry_policy` (there's no error
+/// value to classify), so they are always retried with the default backoff
+/// (even if the underlying type's `default_is_break` is `true`), capped by the
+/// type's `max_delay`.
+impl<E: Retryable> Retryable for AttemptTimeoutError<E> {
+    forward_static_options!(E);
+
+    fn on_not_attempted() -> Option<Self> {
+        E::on_not_attempted().map(Self::Failed)
+    }
+
+    forward_error_options!(match self {
+        Self::Failed(error) => error;
+        Self::TimedOut
+    });
+}
+
+/// Execute a future with retries, cancelling any attempt that doesn't
+/// complete within the given timeout.
+///
+/// A timed-out attempt is treated as a retryable failure, and if the retries
+/// are exhausted (or the loop is stopped by the maximum elapsed time) after a
+/// timeout, `AttemptTimeoutError::TimedOut` is returned. The timeout applies
+/// to each attempt separately, and doesn't include the delays between them.
+pub fn retry_future_with_attempt_timeout<F, Fut, T, E>(
+    mut f: F,
+    timeout: Duration,
+) -> impl Future<Output = Result<T, AttemptTimeoutError<E>>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    retry_with_defaults(move || {
+        let future = tokio::time::timeout(timeout, f());
+
+        async move {
+            match future.await {
+                Ok(Ok(value)) => Ok(value),
+                Ok(Err(error)) => Err(AttemptTimeoutError::Failed(error)),
+                Err(_) => Err(AttemptTimeoutError::TimedOut),
+            }
+        }
+    })
+}
+
+/// A schedule in which the delays between attempts and the timeouts for the
+/// attempts grow together.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GrowthSpec {
+    /// The delay before the first retry.
+    pub initial_delay: Duration,
+    /// The timeout for the first attempt.
+    pub initial_timeout: Duration,
+    /// The factor that each delay and each timeout is multiplied by to get
+    /// the next one.
+    pub multiplier: f64,
+}
+
+/// Execute a future with retries, where each attempt is given more time than
+/// the one before it, as well as waiting longer before it.
+///
+/// This is a common shape for network operations, where a slow response may
+/// mean that the server is overloaded. The spec's initial delay and
+/// multiplier replace the error type's for the default delays (which are
+/// otherwise configured as usual), and the timeouts start at the spec's
+/// initial timeout and grow by the same multiplier. Timeouts are handled as
+/// for `retry_future_with_attempt_timeout`.
+pub fn retry_future_growing<F, Fut, T, E>(
+    mut f: F,
+    spec: GrowthSpec,
+) -> impl Future<Output = Result<T, AttemptTimeoutError<E>>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Debug,
+{
+    let backoff = ErrorBackoff::from_settings(BackoffSettings {
+        initial_delay: spec.initial_delay,
+        multiplier: spec.multiplier,
+        next_delay: None,
+        ..BackoffSettings::for_type::<AttemptTimeoutError<E>>()
+    });
+    let mut timeout = spec.initial_timeout;
+
+    retry_future_with_backoff(
+        move || {
+            let future = tokio::time::timeout(timeout, f());
+            timeout = scale_delay(timeout, spec.multiplier);
+
+            async move {
+                match future.await {
+                    Ok(Ok(value)) => Ok(value),
+                    Ok(Err(error)) => Err(AttemptTimeoutError::Failed(error)),
+                    Err(_) => Err(AttemptTimeoutError::TimedOut),
+                }
+            }
+        },
+        backoff,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestError;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::time::Instant;
+
+    type Error = TestError<100>;
+
+    #[tokio::test(start_paused = true)]
+    async fn attempt_timeout() {
+        let attempts = AtomicU32::new(0);
+        let started = Instant::now();
+        let future = || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(Error::Transient),
+                1 => {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    Ok(1)
+                }
+                _ => Ok(2),
+            }
+        };
+
+        let result = retry_future_with_attempt_timeout(future, Duration::from_secs(1)).await;
+
+        assert_eq!(result, Ok(2));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            started.elapsed(),
+            Duration::from_millis(100) + Duration::from_secs(1) + Duration::from_millis(200)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timed_out() {
+        let attempts = AtomicU32::new(0);
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            std::future::pending::<Result<(), Error>>().await
+        };
+
+        assert_eq!(
+            retry_future_with_attempt_timeout(future, Duration::from_secs(1)).await,
+            Err(AttemptTimeoutError::TimedOut)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+
+        let future = || async { Err::<(), _>(Error::Fatal) };
+
+        assert_eq!(
+            retry_future_with_attempt_timeout(future, Duration::from_secs(1)).await,
+            Err(AttemptTimeoutError::Failed(Error::Fatal))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn growing() {
+        let started = Instant::now();
+        let attempted = std::sync::Mutex::new(vec![]);
+        let future = || async {
+            attempted
+                .lock()
+                .unwrap()
+                .push(started.elapsed().as_millis());
+            std::future::pending::<Result<(), Error>>().await
+        };
+        let spec = GrowthSpec {
+            initial_delay: Duration::from_millis(50),
+            initial_timeout: Duration::from_secs(1),
+            multiplier: 3.0,
+        };
+
+        assert_eq!(
+            retry_future_growing(future, spec).await,
+            Err(AttemptTimeoutError::TimedOut)
+        );
+
+        // The timeouts are 1, 3, 9, and 27 seconds, and the delays are 50,
+        // 150, and 450 milliseconds.
+        assert_eq!(*attempted.lock().unwrap(), vec![0, 1050, 4200, 13650]);
+        assert_eq!(started.elapsed(), Duration::from_millis(40650));
+    }
+}
@@ -0,0 +1,202 @@
+//! Retries for polling operations.
+use crate::forward::{forward_error_options, forward_static_options};
+use crate::{retry_with_defaults, Retryable};
+use std::fmt::{Debug, Display};
+use std::future::Future;
+
+/// The error returned by `retry_until_true`.
+#[derive(Debug, Eq, PartialEq)]
+pub enum UntilTrueError<E> {
+    /// The retries were exhausted while the operation was still returning
+    /// `false`.
+    NotReady,
+    /// The operation failed with the given error.
+    Failed(E),
+}
+
+impl<E: Display> Display for UntilTrueError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotReady => write!(f, "Operation was not ready"),
+            Self::Failed(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for UntilTrueError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::NotReady => None,
+            Self::Failed(error) => Some(error),
+        }
+    }
+}
+
+/// Errors use the configuration of the underlying error type, with the
+/// default backoff for `NotReady` (even if the underlying type's
+/// `default_is_break` is `true`).
+impl<E: Retryable> Retryable for UntilTrueError<E> {
+    forward_static_options!(E);
+
+    fn on_not_attempted() -> Option<Self> {
+        E::on_not_attempted().map(Self::Failed)
+    }
+
+    forward_error_options!(match self {
+        Self::Failed(error) => error;
+        Self::NotReady
+    });
+}
+
+/// Execute a polling operation with retries until it returns `true`.
+///
+/// Errors are retried according to the error type's policy, and `false` is
+/// treated as a "not ready" result that is retried with the default backoff.
+/// If the retries are exhausted, the result of the last attempt is returned,
+/// either as `UntilTrueError::NotReady` or as the error.
+pub fn retry_until_true<F, Fut, E>(mut f: F) -> impl Future<Output = Result<(), UntilTrueError<E>>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<bool, E>>,
+    E: Retryable + Debug,
+{
+    retry_with_defaults(move || {
+        let future = f();
+
+        async move {
+            match future.await {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(UntilTrueError::NotReady),
+                Err(error) => Err(UntilTrueError::Failed(error)),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+    use tryhard::RetryPolicy;
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct Error(bool);
+
+    impl Retryable for Error {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(1)
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            if self.0 {
+                None
+            } else {
+                Some(RetryPolicy::Break)
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn until_true() {
+        let attempts = AtomicU32::new(0);
+
+        let future = || async {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+
+            match attempt {
+                0 => Err(Error(true)),
+                1 | 2 => Ok(false),
+                _ => Ok(true),
+            }
+        };
+
+        assert_eq!(retry_until_true(future).await, Ok(()));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn not_ready() {
+        let attempts = AtomicU32::new(0);
+
+        let future = || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, Error>(false)
+        };
+
+        assert_eq!(
+            retry_until_true(future).await,
+            Err(UntilTrueError::NotReady)
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct StrictError(bool);
+
+    impl Retryable for StrictError {
+        fn max_retries() -> u32 {
+            3
+        }
+
+        fn log_level() -> Option<log::Level> {
+            None
+        }
+
+        fn default_initial_delay() -> Duration {
+            Duration::from_millis(1)
+        }
+
+        fn default_is_break() -> bool {
+            true
+        }
+
+        fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+            self.0
+                .then_some(RetryPolicy::Delay(Duration::from_millis(1)))
+        }
+    }
+
+    #[tokio::test]
+    async fn default_is_break() {
+        let attempts = AtomicU32::new(0);
+
+        let future = || async {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+
+            match attempt {
+                0 => Err(StrictError(true)),
+                1 | 2 => Ok(false),
+                _ => Ok(true),
+            }
+        };
+
+        assert_eq!(retry_until_true(future).await, Ok(()));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+
+        let future = || async { Err(StrictError(false)) };
+
+        assert_eq!(
+            retry_until_true(future).await,
+            Err(UntilTrueError::Failed(StrictError(false)))
+        );
+    }
+
+    #[tokio::test]
+    async fn failed() {
+        let future = || async { Err(Error(false)) };
+
+        assert_eq!(
+            retry_until_true(future).await,
+            Err(UntilTrueError::Failed(Error(false)))
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Limiting retries to a window of the day.
+use std::time::Duration;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A window of the day in which failures may be retried.
+///
+/// The window is given by its start (inclusive) and end (exclusive) as times
+/// since midnight UTC, and it's always checked against the current time in
+/// UTC. A window in local time has to be converted (and adjusted for daylight
+/// saving time if necessary). A window whose start is after its end wraps
+/// past midnight, and one whose start equals its end is always closed.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryWindow {
+    start: Duration,
+    end: Duration,
+    clock: fn() -> Duration,
+}
+
+impl RetryWindow {
+    /// Create a window between the given times since midnight UTC, which is
+    /// checked against the system clock.
+    pub const fn utc(start: Duration, end: Duration) -> Self {
+        Self {
+            start,
+            end,
+            clock: utc_time_of_day,
+        }
+    }
+
+    /// Use the given function in place of the system clock to get the current
+    /// time since midnight UTC.
+    ///
+    /// This is mostly useful for testing.
+    pub const fn with_clock(self, clock: fn() -> Duration) -> Self {
+        Self { clock, ..self }
+    }
+
+    /// Indicate whether a time since midnight UTC is in the window.
+    pub fn contains(&self, time_of_day: Duration) -> bool {
+        if self.start <= self.end {
+            self.start <= time_of_day && time_of_day < self.end
+        } else {
+            self.start <= time_of_day || time_of_day < self.end
+        }
+    }
+
+    /// Indicate whether the current time (according to the window's clock) is
+    /// in the window.
+    pub fn is_open(&self) -> bool {
+        self.contains((self.clock)())
+    }
+}
+
+/// Return the time since midnight UTC according to the system clock.
+///
+/// Leap seconds aren't counted, since the system clock doesn't count them.
+fn utc_time_of_day() -> Duration {
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+
+    Duration::from_nanos((since_epoch.as_nanos() % DAY.as_nanos()) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hours(hours: u64) -> Duration {
+        Duration::from_secs(hours * 60 * 60)
+    }
+
+    #[test]
+    fn contains() {
+        let business_hours = RetryWindow::utc(hours(9), hours(17));
+        let overnight = RetryWindow::utc(hours(22), hours(6));
+
+        assert!(!business_hours.contains(hours(3)));
+        assert!(business_hours.contains(hours(9)));
+        assert!(business_hours.contains(hours(12)));
+        assert!(!business_hours.contains(hours(17)));
+
+        assert!(overnight.contains(hours(3)));
+        assert!(!overnight.contains(hours(12)));
+        assert!(overnight.contains(hours(23)));
+
+        assert!(!RetryWindow::utc(hours(1), hours(1)).contains(hours(1)));
+        assert!(RetryWindow::utc(Duration::ZERO, DAY).is_open());
+    }
+
+    #[test]
+    fn with_clock() {
+        let business_hours = RetryWindow::utc(hours(9), hours(17));
+
+        assert!(business_hours.with_clock(|| hours(12)).is_open());
+        assert!(!business_hours.with_clock(|| hours(20)).is_open());
+    }
+}
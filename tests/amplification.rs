@@ -0,0 +1,50 @@
+use retryable_error::{reset_retry_amplification, retry_amplification, retry_future, Retryable};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use tryhard::RetryPolicy;
+
+#[derive(Debug, Eq, PartialEq)]
+struct Error;
+
+impl Retryable for Error {
+    fn max_retries() -> u32 {
+        3
+    }
+
+    fn default_initial_delay() -> Duration {
+        Duration::from_millis(1)
+    }
+
+    fn log_level() -> Option<log::Level> {
+        None
+    }
+
+    fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+        None
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn amplification() {
+    reset_retry_amplification();
+    assert_eq!(retry_amplification(), 1.0);
+
+    for _ in 0..2 {
+        assert_eq!(retry_future(|| async { Ok::<_, Error>(()) }).await, Ok(()));
+    }
+    assert_eq!(retry_amplification(), 1.0);
+
+    // One operation that uses all of its retries brings the total to six
+    // attempts for three operations.
+    let attempts = AtomicU32::new(0);
+    let future = || async {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        Err::<(), _>(Error)
+    };
+    assert_eq!(retry_future(future).await, Err(Error));
+    assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    assert_eq!(retry_amplification(), 2.0);
+
+    reset_retry_amplification();
+    assert_eq!(retry_amplification(), 1.0);
+}
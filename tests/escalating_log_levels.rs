@@ -0,0 +1,81 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use retryable_error::{retry_future, Retryable};
+use std::sync::Mutex;
+use std::time::Duration;
+use tryhard::RetryPolicy;
+
+/// A logger that records the level and message of every record.
+struct Capture(Mutex<Vec<(Level, String)>>);
+
+impl Log for Capture {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Capture = Capture(Mutex::new(Vec::new()));
+
+#[derive(Debug)]
+struct Error;
+
+impl Retryable for Error {
+    fn max_retries() -> u32 {
+        5
+    }
+
+    fn default_initial_delay() -> Duration {
+        Duration::from_millis(1)
+    }
+
+    fn log_level() -> Option<Level> {
+        Some(Level::Error)
+    }
+
+    fn escalating_log_levels() -> Option<&'static [Level]> {
+        Some(&[Level::Info, Level::Info, Level::Warn])
+    }
+
+    fn log_error_value() -> bool {
+        false
+    }
+
+    fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+        None
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn escalating_log_levels() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    assert!(retry_future(|| async { Err::<(), _>(Error) })
+        .await
+        .is_err());
+
+    let records = LOGGER.0.lock().unwrap();
+    let retries = records
+        .iter()
+        .filter(|(_, message)| message.starts_with("Retry "))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        retries,
+        [
+            &(Level::Info, "Retry 1; waiting 1ms".to_string()),
+            &(Level::Info, "Retry 2; waiting 2ms".to_string()),
+            &(Level::Warn, "Retry 3; waiting 4ms".to_string()),
+            &(Level::Warn, "Retry 4; waiting 8ms".to_string()),
+            &(Level::Warn, "Retry 5; waiting 16ms".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,48 @@
+use retryable_error::{
+    retries_enabled, retry_future, retry_future_detailed, retry_future_result, set_retries_enabled,
+    RetryError, SimpleRetryable, StopReason,
+};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+#[derive(Debug)]
+struct Error;
+
+impl SimpleRetryable for Error {
+    const MAX_RETRIES: u32 = 3;
+    const INITIAL_DELAY_MS: u64 = 1;
+}
+
+async fn count_attempts() -> u32 {
+    let attempts = AtomicU32::new(0);
+    let future = || async {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        Err::<(), _>(Error)
+    };
+
+    assert!(retry_future(future).await.is_err());
+
+    attempts.load(Ordering::SeqCst)
+}
+
+#[tokio::test]
+async fn kill_switch() {
+    assert!(retries_enabled());
+    assert_eq!(count_attempts().await, 4);
+
+    set_retries_enabled(false);
+    assert!(!retries_enabled());
+    assert_eq!(count_attempts().await, 1);
+
+    let failure = retry_future_detailed(None, || async { Err::<(), _>(Error) })
+        .await
+        .unwrap_err();
+    assert_eq!(failure.reason, StopReason::Disabled);
+
+    let error = retry_future_result(|| async { Err::<(), _>(Error) })
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RetryError::Disabled { error: Error }));
+
+    set_retries_enabled(true);
+    assert_eq!(count_attempts().await, 4);
+}
@@ -0,0 +1,116 @@
+use retryable_error::{
+    retry_future, retry_future_abortable, retry_future_async_policy, retry_future_dry_run,
+    retry_future_pausable, retry_future_with_backoff, retry_future_with_ctx, retry_operation,
+    set_retries_enabled, AsyncRetryable, PauseSwitch, RetrySession, Retryable,
+};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tryhard::RetryPolicy;
+
+// The kill switch is global, so the tests that use it can't run concurrently.
+static KILL_SWITCH: Mutex<()> = Mutex::const_new(());
+
+#[derive(Debug, Eq, PartialEq)]
+enum Error {
+    Failed,
+    NotAttempted,
+}
+
+impl Retryable for Error {
+    fn max_retries() -> u32 {
+        3
+    }
+
+    fn default_initial_delay() -> Duration {
+        Duration::from_millis(1)
+    }
+
+    fn log_level() -> Option<log::Level> {
+        None
+    }
+
+    fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+        None
+    }
+
+    fn on_not_attempted() -> Option<Self> {
+        Some(Self::NotAttempted)
+    }
+}
+
+impl AsyncRetryable for Error {
+    async fn custom_retry_policy(&self) -> Option<RetryPolicy> {
+        None
+    }
+}
+
+struct Ctx;
+
+impl retryable_error::RetryContext<Error> for Ctx {}
+
+#[tokio::test]
+async fn not_attempted_with_kill_switch() {
+    let _guard = KILL_SWITCH.lock().await;
+    let attempts = AtomicU32::new(0);
+    let future = || async {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        Err::<(), _>(Error::Failed)
+    };
+
+    set_retries_enabled(false);
+    assert_eq!(
+        retry_future_with_backoff(future, Error::new_backoff()).await,
+        Err(Error::NotAttempted)
+    );
+    assert_eq!(attempts.load(Ordering::SeqCst), 0);
+
+    // The plain `tryhard` loop always makes the first attempt.
+    assert_eq!(retry_future(future).await, Err(Error::Failed));
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+    set_retries_enabled(true);
+    assert_eq!(
+        retry_future_with_backoff(future, Error::new_backoff()).await,
+        Err(Error::Failed)
+    );
+    assert_eq!(attempts.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn not_attempted_in_own_loops() {
+    let _guard = KILL_SWITCH.lock().await;
+    let attempts = AtomicU32::new(0);
+    let future = || async {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        Err::<(), _>(Error::Failed)
+    };
+    let (_sender, mut signals) = tokio::sync::mpsc::channel(1);
+
+    set_retries_enabled(false);
+    assert_eq!(retry_operation(future).await, Err(Error::NotAttempted));
+    assert_eq!(
+        retry_future_abortable(future, &mut signals, |_| true).await,
+        Err(Error::NotAttempted)
+    );
+    assert_eq!(
+        retry_future_async_policy(future).await,
+        Err(Error::NotAttempted)
+    );
+    assert_eq!(
+        retry_future_with_ctx(&mut Ctx, future).await,
+        Err(Error::NotAttempted)
+    );
+    assert_eq!(retry_future_dry_run(future).await, Err(Error::NotAttempted));
+    assert_eq!(
+        retry_future_pausable(future, &PauseSwitch::new()).await,
+        Err(Error::NotAttempted)
+    );
+    assert_eq!(
+        RetrySession::new().run(future).await,
+        Err(Error::NotAttempted)
+    );
+    set_retries_enabled(true);
+
+    assert_eq!(attempts.load(Ordering::SeqCst), 0);
+}